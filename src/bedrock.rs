@@ -0,0 +1,240 @@
+#![allow(unused)]
+
+use std::io;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tokio::net::UdpSocket;
+use tokio::time;
+
+use crate::dns::try_resolved;
+use crate::error::{Phase, PingError};
+use crate::protocol::{connect_udp, PingOptions, port_or};
+
+pub const BEDROCK_DEFAULT_PORT: u16 = 19132;
+
+/// RakNet offline message data ID
+pub const OFFLINE_MAGIC: [u8; 16] = [0x00, 0xFF, 0xFF, 0x00, 0xFE, 0xFE, 0xFE, 0xFE, 0xFD, 0xFD, 0xFD, 0xFD, 0x12, 0x34, 0x56, 0x78];
+
+const UNCONNECTED_PING: u8 = 0x01;
+const UNCONNECTED_PONG: u8 = 0x1C;
+
+/// Server ID string of the Unconnected Pong,
+/// `edition;motd;protocol;version;online;max;guid;level;gamemode;gamemode id;port v4;port v6;`
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct BedrockStatus {
+    /// `MCPE` or `MCEE` for Education Edition
+    pub edition: String,
+    pub motd: String,
+    pub protocol: i32,
+    pub version: String,
+    pub online_players: i64,
+    pub max_players: i64,
+    pub server_guid: Option<u64>,
+    /// Second MOTD line, the level name on vanilla servers
+    pub level_name: Option<String>,
+    pub gamemode: Option<String>,
+    pub gamemode_id: Option<i32>,
+    pub port_v4: Option<u16>,
+    pub port_v6: Option<u16>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BedrockResult {
+    pub status: BedrockStatus,
+    /// Server ID string as received
+    pub raw: String,
+    /// GUID from the pong header, independent of the one in the server ID string
+    pub server_guid: u64,
+    pub latency: Duration,
+}
+
+pub fn compose_unconnected_ping_packet(time: i64, client_guid: i64) -> Vec<u8> {
+    // Packet ID. Time as long. Magic. Client GUID as long.
+    let mut content: Vec<u8> = Vec::with_capacity(33);
+    content.push(UNCONNECTED_PING);
+    content.extend_from_slice(&time.to_be_bytes());
+    content.extend_from_slice(&OFFLINE_MAGIC);
+    content.extend_from_slice(&client_guid.to_be_bytes());
+    content
+}
+
+/// Returns echoed time, server GUID and server ID string of an Unconnected Pong
+pub fn parse_unconnected_pong(packet: &[u8]) -> Result<(i64, u64, String), PingError> {
+    let malformed = || PingError::MalformedBedrockResponse(String::from_utf8_lossy(packet).to_string());
+    if packet.len() < 35 {
+        return Err(malformed());
+    }
+    if packet[0] != UNCONNECTED_PONG {
+        return Err(PingError::UnexpectedPacketId { expected: UNCONNECTED_PONG as i32, actual: packet[0] as i32 });
+    }
+    let time = i64::from_be_bytes(packet[1..9].try_into().unwrap());
+    let guid = u64::from_be_bytes(packet[9..17].try_into().unwrap());
+    if packet[17..33] != OFFLINE_MAGIC {
+        return Err(malformed());
+    }
+    let strlen = u16::from_be_bytes([packet[33], packet[34]]) as usize;
+    let server_id = packet.get(35..35 + strlen).ok_or_else(malformed)?;
+    let server_id = String::from_utf8(server_id.to_vec()).map_err(|_| malformed())?;
+    Ok((time, guid, server_id))
+}
+
+pub fn parse_server_id(server_id: &str) -> Result<BedrockStatus, PingError> {
+    let malformed = || PingError::MalformedBedrockResponse(server_id.to_string());
+    let fields: Vec<&str> = server_id.split(';').collect();
+    if fields.len() < 6 {
+        return Err(malformed());
+    }
+    let optional = |i: usize| fields.get(i).filter(|v| !v.is_empty()).map(|v| v.to_string());
+    Ok(BedrockStatus {
+        edition: fields[0].to_string(),
+        motd: fields[1].to_string(),
+        protocol: i32::from_str(fields[2]).map_err(|_| malformed())?,
+        version: fields[3].to_string(),
+        online_players: i64::from_str(fields[4]).map_err(|_| malformed())?,
+        max_players: i64::from_str(fields[5]).map_err(|_| malformed())?,
+        server_guid: optional(6).and_then(|v| u64::from_str(&v).ok()),
+        level_name: optional(7),
+        gamemode: optional(8),
+        gamemode_id: optional(9).and_then(|v| i32::from_str(&v).ok()),
+        port_v4: optional(10).and_then(|v| u16::from_str(&v).ok()),
+        port_v6: optional(11).and_then(|v| u16::from_str(&v).ok()),
+    })
+}
+
+pub async fn bedrock_ping(host: &str, port: u16, options: &PingOptions) -> Result<BedrockResult, PingError> {
+    if options.timeout == 0 {
+        return Err(PingError::InvalidTimeout);
+    }
+    let dur = options.timeout_duration();
+    try_resolved(host, port_or(port, BEDROCK_DEFAULT_PORT), options.ip_mode, |addr| async move { bedrock_exchange(&addr, dur).await }).await
+}
+
+async fn bedrock_exchange(addr: &SocketAddr, dur: Duration) -> Result<BedrockResult, PingError> {
+    let socket = connect_udp(addr).await?;
+
+    let sent_time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or_default();
+    let client_guid = sent_time ^ ((std::process::id() as i64) << 32);
+    let sent_at = Instant::now();
+    socket.send(&compose_unconnected_ping_packet(sent_time, client_guid)).await.map_err(|e| PingError::io(Phase::Handshake, e))?;
+
+    let mut buf = [0u8; 1500];
+    let len = match time::timeout(dur, socket.recv(&mut buf)).await {
+        Ok(Ok(len)) => len,
+        // ICMP port unreachable surfaces as a refused connection
+        Ok(Err(e)) if e.kind() == io::ErrorKind::ConnectionRefused => return Err(PingError::ConnectionRefused(*addr)),
+        Ok(Err(e)) => return Err(PingError::io(Phase::Status, e)),
+        Err(_) => return Err(PingError::Timeout(Phase::Status)),
+    };
+    let latency = sent_at.elapsed();
+
+    let (echoed, server_guid, raw) = parse_unconnected_pong(&buf[..len])?;
+    if echoed != sent_time {
+        return Err(PingError::PongMismatch { sent: sent_time, received: echoed });
+    }
+    let status = parse_server_id(&raw)?;
+    Ok(BedrockResult { status, raw, server_guid, latency })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compose_unconnected_pong(time: i64, guid: u64, server_id: &str) -> Vec<u8> {
+        let mut content = vec![UNCONNECTED_PONG];
+        content.extend_from_slice(&time.to_be_bytes());
+        content.extend_from_slice(&guid.to_be_bytes());
+        content.extend_from_slice(&OFFLINE_MAGIC);
+        content.extend_from_slice(&(server_id.len() as u16).to_be_bytes());
+        content.extend_from_slice(server_id.as_bytes());
+        content
+    }
+
+    #[test]
+    fn test_compose_unconnected_ping_packet() {
+        let t = compose_unconnected_ping_packet(1, 2);
+        assert_eq!(33, t.len());
+        assert_eq!(UNCONNECTED_PING, t[0]);
+        assert_eq!(1i64.to_be_bytes(), t[1..9]);
+        assert_eq!(OFFLINE_MAGIC, t[9..25]);
+        assert_eq!(2i64.to_be_bytes(), t[25..33]);
+    }
+
+    #[test]
+    fn test_parse_server_id() {
+        let res = parse_server_id("MCPE;Dedicated Server;819;1.21.93;2;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;");
+        assert!(res.is_ok());
+        let res = res.unwrap();
+        assert_eq!("MCPE", res.edition);
+        assert_eq!("Dedicated Server", res.motd);
+        assert_eq!(819, res.protocol);
+        assert_eq!("1.21.93", res.version);
+        assert_eq!(2, res.online_players);
+        assert_eq!(10, res.max_players);
+        assert_eq!(Some(13253860892328930865), res.server_guid);
+        assert_eq!(Some("Bedrock level".to_string()), res.level_name);
+        assert_eq!(Some("Survival".to_string()), res.gamemode);
+        assert_eq!(Some(1), res.gamemode_id);
+        assert_eq!(Some(19132), res.port_v4);
+        assert_eq!(Some(19133), res.port_v6);
+
+        let res = parse_server_id("MCPE;Geyser;390;1.14.60;0;100");
+        assert!(res.is_ok());
+        let res = res.unwrap();
+        assert!(res.server_guid.is_none());
+        assert!(res.level_name.is_none());
+        assert!(res.port_v4.is_none());
+
+        assert!(parse_server_id("MCPE;motd;390;1.14.60;0").is_err());
+        assert!(parse_server_id("MCPE;motd;x;1.14.60;0;10").is_err());
+    }
+
+    #[test]
+    fn test_parse_unconnected_pong() {
+        let pong = compose_unconnected_pong(42, 7, "MCPE;motd;390;1.14.60;0;10;");
+        let res = parse_unconnected_pong(&pong);
+        assert!(res.is_ok());
+        assert_eq!((42, 7, "MCPE;motd;390;1.14.60;0;10;".to_string()), res.unwrap());
+
+        assert!(parse_unconnected_pong(&pong[..pong.len() - 1]).is_err());
+        assert!(parse_unconnected_pong(&pong[..20]).is_err());
+        let mut bad_magic = pong.clone();
+        bad_magic[21] = 0x00;
+        assert!(matches!(parse_unconnected_pong(&bad_magic), Err(PingError::MalformedBedrockResponse(_))));
+        let mut bad_id = pong.clone();
+        bad_id[0] = 0x1D;
+        assert!(matches!(parse_unconnected_pong(&bad_id), Err(PingError::UnexpectedPacketId { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_bedrock_ping() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            while let Ok((len, from)) = server.recv_from(&mut buf).await {
+                if len != 33 || buf[0] != UNCONNECTED_PING {
+                    continue
+                }
+                let time = i64::from_be_bytes(buf[1..9].try_into().unwrap());
+                let pong = compose_unconnected_pong(time, 99, "MCPE;Test;819;1.21.93;1;5;99;World;Creative;1;19132;19133;");
+                let _ = server.send_to(&pong, from).await;
+            }
+        });
+
+        let options = PingOptions { timeout: 2, ..Default::default() };
+        let res = bedrock_ping("127.0.0.1", port, &options).await;
+        assert!(res.is_ok());
+        let res = res.unwrap();
+        assert_eq!(99, res.server_guid);
+        assert_eq!("Test", res.status.motd);
+        assert_eq!(Some("World".to_string()), res.status.level_name);
+
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = silent.local_addr().unwrap().port();
+        let options = PingOptions { timeout: 1, ..Default::default() };
+        assert!(matches!(bedrock_ping("127.0.0.1", port, &options).await, Err(PingError::Timeout(Phase::Status))));
+    }
+}
@@ -1,24 +1,54 @@
 #![allow(unused)]
 
-use std::net::{IpAddr, Ipv4Addr};
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::str::FromStr;
 use std::sync::OnceLock;
 
 use hickory_resolver::name_server::TokioConnectionProvider;
 use hickory_resolver::Resolver;
 
+use crate::error::PingError;
 use crate::protocol::DEFAULT_PORT;
 
-fn get_resolver() -> &'static Resolver<TokioConnectionProvider> {
-    static RESOLVER: OnceLock<Resolver<TokioConnectionProvider>> = OnceLock::new();
+/// Which address families to resolve and connect to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IpMode {
+    V4Only,
+    V6Only,
+    /// IPv4 addresses first, then IPv6
+    #[default]
+    Dual,
+}
+
+impl IpMode {
+    fn allows(&self, ip: &IpAddr) -> bool {
+        match self {
+            IpMode::V4Only => ip.is_ipv4(),
+            IpMode::V6Only => ip.is_ipv6(),
+            IpMode::Dual => true,
+        }
+    }
+}
+
+/// System resolver, `None` when its configuration cannot be read so that lookups fail with `PingError::Resolve`
+fn get_resolver() -> Option<&'static Resolver<TokioConnectionProvider>> {
+    static RESOLVER: OnceLock<Option<Resolver<TokioConnectionProvider>>> = OnceLock::new();
     RESOLVER.get_or_init(|| {
-       Resolver::builder_tokio().unwrap().build()
-    })
+       Resolver::builder_tokio().ok().map(|v| v.build())
+    }).as_ref()
 }
 
 async fn resolve_ipv4(host: &str) -> Vec<Ipv4Addr> {
+    #[cfg(any(test, feature = "test-util"))]
+    if let Some(ips) = crate::testing::stub_ipv4(host) {
+        return ips;
+    }
     let mut ips = Vec::new();
-    let res = Resolver::ipv4_lookup(get_resolver(), host).await;
+    let Some(resolver) = get_resolver() else {
+        return ips;
+    };
+    let res = Resolver::ipv4_lookup(resolver, host).await;
     if res.is_err() {
         return ips
     }
@@ -29,101 +59,233 @@ async fn resolve_ipv4(host: &str) -> Vec<Ipv4Addr> {
     ips
 }
 
-async fn resolve_srv(host: &str) -> Vec<(Ipv4Addr, u16)> {
+async fn resolve_ipv6(host: &str) -> Vec<Ipv6Addr> {
+    #[cfg(any(test, feature = "test-util"))]
+    if let Some(ips) = crate::testing::stub_ipv6(host) {
+        return ips;
+    }
     let mut ips = Vec::new();
-    let res = Resolver::srv_lookup(get_resolver(), format!("_minecraft._tcp.{host}")).await;
-    if res.is_err() {
+    let Some(resolver) = get_resolver() else {
         return ips;
+    };
+    let res = Resolver::ipv6_lookup(resolver, host).await;
+    if res.is_err() {
+        return ips
     }
     let res = res.unwrap();
-    for srv_rec in res.iter() {
-        let res4 = resolve_ipv4(srv_rec.target().to_string().as_str()).await;
-        if !res4.is_empty() {
-            res4.iter().for_each(|v| {
-                ips.push((*v, srv_rec.port()));
-            });
-        }
+    res.iter().for_each(|aaaa_rec| {
+        ips.push(aaaa_rec.0);
+    });
+    ips
+}
+
+async fn resolve_ip(host: &str, mode: IpMode) -> Vec<IpAddr> {
+    let mut ips = Vec::new();
+    if mode != IpMode::V6Only {
+        resolve_ipv4(host).await.into_iter().for_each(|v| ips.push(IpAddr::V4(v)));
+    }
+    if mode != IpMode::V4Only {
+        resolve_ipv6(host).await.into_iter().for_each(|v| ips.push(IpAddr::V6(v)));
     }
     ips
 }
 
-pub async fn resolve(host: &str, port: &u16) -> Result<Vec<(Ipv4Addr, u16)>, String> {
+async fn lookup_srv(host: &str) -> Vec<(String, u16)> {
+    #[cfg(any(test, feature = "test-util"))]
+    if let Some(targets) = crate::testing::stub_srv(host) {
+        return targets;
+    }
+    let Some(resolver) = get_resolver() else {
+        return Vec::new();
+    };
+    let res = Resolver::srv_lookup(resolver, format!("_minecraft._tcp.{host}")).await;
+    if res.is_err() {
+        return Vec::new();
+    }
+    res.unwrap().iter().map(|srv_rec| (srv_rec.target().to_string(), srv_rec.port())).collect()
+}
+
+async fn resolve_srv(host: &str, mode: IpMode) -> Vec<SocketAddr> {
+    let mut ips = Vec::new();
+    for (target, port) in lookup_srv(host).await {
+        let res_ip = resolve_ip(target.as_str(), mode).await;
+        res_ip.iter().for_each(|v| {
+            ips.push(SocketAddr::new(*v, port));
+        });
+    }
+    ips
+}
+
+/// Strips the brackets around an IPv6 literal, `[::1]` becomes `::1`
+pub fn unbracket(host: &str) -> &str {
+    host.strip_prefix('[').and_then(|v| v.strip_suffix(']')).unwrap_or(host)
+}
+
+/// Splits `host:port`, `[v6]:port`, `[v6]` or a bare host into host and optional port.
+/// A bare IPv6 literal such as `::1` is returned as is.
+pub fn split_host_port(s: &str) -> Result<(String, Option<u16>), PingError> {
+    let invalid = || PingError::InvalidHost(s.to_string());
+    if let Some(rest) = s.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']').ok_or_else(invalid)?;
+        if rest.is_empty() {
+            return Ok((host.to_string(), None));
+        }
+        let port = rest.strip_prefix(':').ok_or_else(invalid)?;
+        let port = u16::from_str(port).map_err(|_| invalid())?;
+        return Ok((host.to_string(), Some(port)));
+    }
+    match s.split_once(':') {
+        Some((host, port)) if !port.contains(':') => {
+            let port = u16::from_str(port).map_err(|_| invalid())?;
+            Ok((host.to_string(), Some(port)))
+        }
+        _ => Ok((s.to_string(), None)),
+    }
+}
+
+pub async fn resolve(host: &str, port: &u16, mode: IpMode) -> Result<Vec<SocketAddr>, PingError> {
+    let host = unbracket(host);
     let res = IpAddr::from_str(host);
     if let Ok(res_ip) = res {
-        return match res_ip {
-            IpAddr::V4(res4) => { Ok(vec![(res4, *port)]) },
-            IpAddr::V6(_) => { Err("IPv6 not supported".to_string()) }
+        if !mode.allows(&res_ip) {
+            return Err(PingError::AddressFamily(res_ip, mode));
         }
+        return Ok(vec![SocketAddr::new(res_ip, *port)]);
     }
     if *port == DEFAULT_PORT {
-        let res = resolve_srv(host).await;
+        let res = resolve_srv(host, mode).await;
         if !res.is_empty() {
             return Ok(res)
         }
     }
-    let res4 = resolve_ipv4(host).await;
-    let mut res = Vec::new();
-    res4.iter().for_each(|v| {
-        res.push((*v, *port));
-    });
+    let res: Vec<SocketAddr> = resolve_ip(host, mode).await.into_iter().map(|v| SocketAddr::new(v, *port)).collect();
     if res.is_empty() {
-        return Err(format!("Cannot resolve \"{host}\""))
+        return Err(PingError::Resolve(host.to_string()))
     }
     Ok(res)
 }
 
+/// Resolves the host and tries each address with `f` until one succeeds, failing with the last error otherwise
+pub(crate) async fn try_resolved<T, F, Fut>(host: &str, port: u16, mode: IpMode, mut f: F) -> Result<T, PingError>
+where
+    F: FnMut(SocketAddr) -> Fut,
+    Fut: Future<Output = Result<T, PingError>>,
+{
+    if host.is_empty() {
+        return Err(PingError::InvalidHost(host.to_string()));
+    }
+    let ips = resolve(host, &port, mode).await?;
+
+    let mut last_err = PingError::Resolve(host.to_string());
+    for v in ips {
+        match f(v).await {
+            Ok(res) => return Ok(res),
+            Err(e) => last_err = e,
+        }
+    }
+    Err(last_err)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::protocol::DEFAULT_PORT;
+    use crate::testing::StubResolver;
 
     use super::*;
 
+    #[test]
+    fn test_split_host_port() {
+        assert_eq!(("localhost".to_string(), None), split_host_port("localhost").unwrap());
+        assert_eq!(("localhost".to_string(), Some(25566)), split_host_port("localhost:25566").unwrap());
+        assert_eq!(("127.0.0.1".to_string(), Some(25565)), split_host_port("127.0.0.1:25565").unwrap());
+        assert_eq!(("::1".to_string(), None), split_host_port("::1").unwrap());
+        assert_eq!(("::1".to_string(), None), split_host_port("[::1]").unwrap());
+        assert_eq!(("::1".to_string(), Some(25565)), split_host_port("[::1]:25565").unwrap());
+        assert_eq!(("2001:db8::1".to_string(), Some(1)), split_host_port("[2001:db8::1]:1").unwrap());
+        assert!(split_host_port("[::1").is_err());
+        assert!(split_host_port("[::1]25565").is_err());
+        assert!(split_host_port("[::1]:").is_err());
+        assert!(split_host_port("localhost:port").is_err());
+        assert!(split_host_port("localhost:65536").is_err());
+    }
+
+    #[test]
+    fn test_unbracket() {
+        assert_eq!("::1", unbracket("[::1]"));
+        assert_eq!("::1", unbracket("::1"));
+        assert_eq!("localhost", unbracket("localhost"));
+        assert_eq!("[::1", unbracket("[::1"));
+    }
+
     #[tokio::test]
-    async fn test_resolve() {
-        let r = resolve("::1", &DEFAULT_PORT).await;
-        assert!(r.is_err());
-        let r = resolve("nope", &DEFAULT_PORT).await;
-        assert!(r.is_err());
+    async fn test_resolve_literal() {
+        let r = resolve("::1", &DEFAULT_PORT, IpMode::Dual).await;
+        assert!(r.is_ok());
+        assert_eq!(vec![SocketAddr::from_str("[::1]:25565").unwrap()], r.unwrap());
+        let r = resolve("[::1]", &25566u16, IpMode::V6Only).await;
+        assert!(r.is_ok());
+        assert_eq!(vec![SocketAddr::from_str("[::1]:25566").unwrap()], r.unwrap());
+        assert!(matches!(resolve("::1", &DEFAULT_PORT, IpMode::V4Only).await, Err(PingError::AddressFamily(_, IpMode::V4Only))));
+        assert!(matches!(resolve("127.0.0.1", &DEFAULT_PORT, IpMode::V6Only).await, Err(PingError::AddressFamily(_, IpMode::V6Only))));
 
-        let r = resolve("127.0.0.2", &25562u16).await;
+        let r = resolve("127.0.0.2", &25562u16, IpMode::Dual).await;
         assert!(r.is_ok());
-        assert!(r.unwrap().contains(&(Ipv4Addr::from_str("127.0.0.2").unwrap(), 25562u16)));
+        assert!(r.unwrap().contains(&SocketAddr::from_str("127.0.0.2:25562").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let _stub = StubResolver::new()
+            .ip("localhost", IpAddr::from([127, 0, 0, 1]))
+            .srv("play.cubecraft.net", "mc.cubecraft.net.", DEFAULT_PORT)
+            .ip("mc.cubecraft.net", IpAddr::from([198, 51, 100, 10]))
+            .srv("critz.gg", "critz.gg.", DEFAULT_PORT)
+            .ip("critz.gg", IpAddr::from([198, 51, 100, 20]))
+            .ip("mc.hypixel.net", IpAddr::from([198, 51, 100, 30]))
+            .ip("mc.hypixel.net", IpAddr::from([198, 51, 100, 31]))
+            .ip("mc.hypixel.net", IpAddr::from([0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x30]))
+            .unresolvable("nope")
+            .install();
+
+        let r = resolve("nope", &DEFAULT_PORT, IpMode::Dual).await;
+        assert!(matches!(r, Err(PingError::Resolve(_))));
 
-        let r = resolve("localhost", &25563u16).await;
+        let r = resolve("localhost", &25563u16, IpMode::V4Only).await;
         assert!(r.is_ok());
-        assert!(r.unwrap().contains(&(Ipv4Addr::from_str("127.0.0.1").unwrap(), 25563u16)));
+        assert!(r.unwrap().contains(&SocketAddr::from_str("127.0.0.1:25563").unwrap()));
 
-        let r = resolve("play.cubecraft.net", &DEFAULT_PORT).await;
+        let r = resolve("play.cubecraft.net", &DEFAULT_PORT, IpMode::Dual).await;
         assert!(r.is_ok());
         let r = r.unwrap();
         assert!(!r.is_empty());
         r.iter().for_each(|v| {
-            assert_eq!(v.1, DEFAULT_PORT);
+            assert_eq!(v.port(), DEFAULT_PORT);
         });
 
-        let r = resolve("critz.gg", &25564u16).await;
+        let r = resolve("critz.gg", &25564u16, IpMode::Dual).await;
         assert!(r.is_ok());
         let r = r.unwrap();
         assert!(!r.is_empty());
         r.iter().for_each(|v| {
-            assert_eq!(v.1, 25564u16);
+            assert_eq!(v.port(), 25564u16);
         });
 
-        let r = resolve("critz.gg", &DEFAULT_PORT).await;
+        let r = resolve("critz.gg", &DEFAULT_PORT, IpMode::Dual).await;
         assert!(r.is_ok());
         let r = r.unwrap();
         assert!(!r.is_empty());
         r.iter().for_each(|v| {
-            assert_eq!(v.1, DEFAULT_PORT);
+            assert_eq!(v.port(), DEFAULT_PORT);
         });
 
-        let r = resolve("mc.hypixel.net", &DEFAULT_PORT).await;
+        let r = resolve("mc.hypixel.net", &DEFAULT_PORT, IpMode::V4Only).await;
         assert!(r.is_ok());
         let r = r.unwrap();
         assert!(!r.is_empty());
         assert!(r.len() > 1);
         r.iter().for_each(|v| {
-            assert_eq!(v.1, DEFAULT_PORT);
+            assert!(v.is_ipv4());
+            assert_eq!(v.port(), DEFAULT_PORT);
         });
     }
 }
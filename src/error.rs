@@ -0,0 +1,211 @@
+#![allow(unused)]
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::dns::IpMode;
+use crate::protocol::ProtocolNum;
+
+/// Step of the exchange an I/O failure or timeout happened in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Connect,
+    /// Sending the handshake and status request
+    Handshake,
+    /// Reading the status response
+    Status,
+    /// Ping request and pong response
+    Ping,
+    /// Reading the answer to Login Start
+    Login,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Phase::Connect => "connect",
+            Phase::Handshake => "handshake",
+            Phase::Status => "status",
+            Phase::Ping => "ping",
+            Phase::Login => "login",
+        };
+        f.write_str(s)
+    }
+}
+
+#[derive(Debug)]
+pub enum PingError {
+    InvalidHost(String),
+    InvalidTimeout,
+    UnknownProtocol(ProtocolNum),
+    /// No usable address found for the host
+    Resolve(String),
+    /// IP literal of a family excluded by the IP mode
+    AddressFamily(IpAddr, IpMode),
+    ConnectionRefused(SocketAddr),
+    Connect(SocketAddr, io::Error),
+    Timeout(Phase),
+    Io(Phase, io::Error),
+    /// Connection closed before the announced data arrived
+    Truncated(Phase),
+    MalformedVarInt,
+    /// String that is not valid UTF-8
+    MalformedString,
+    MalformedUuid(String),
+    InvalidLength(i64),
+    UnexpectedPacketId { expected: i32, actual: i32 },
+    /// Handshake next state other than status, login or transfer
+    UnknownIntent(i32),
+    OversizedFrame { size: usize, max: usize },
+    InvalidJson(serde_json::Error),
+    PongMismatch { sent: i64, received: i64 },
+    MalformedLegacyResponse(String),
+    MalformedBedrockResponse(String),
+    MalformedQueryResponse(String),
+    /// `modinfo` or `forgeData` not in the layout Forge sends
+    MalformedForgeData(String),
+    /// PROXY protocol header that is neither a valid v1 line nor v2 binary header
+    MalformedProxyHeader(String),
+    /// SOCKS5 or HTTP proxy rejecting the credentials, or asking for some when none are given
+    ProxyAuthFailed,
+    /// Request that cannot be expressed to the proxy, such as a SOCKS5 field over 255 bytes
+    InvalidProxyRequest(String),
+    /// Proxy failing to connect to the server, with its reason
+    ProxyRefused(String),
+    /// Answer of the proxy not following SOCKS5 or HTTP
+    MalformedProxyResponse(String),
+}
+
+impl PingError {
+    /// True when the server could not be reached at all,
+    /// as opposed to answering with something unexpected
+    pub fn is_unreachable(&self) -> bool {
+        matches!(self, PingError::Resolve(_)
+            | PingError::ConnectionRefused(_)
+            | PingError::Connect(..)
+            | PingError::Timeout(Phase::Connect)
+            | PingError::ProxyRefused(_))
+    }
+
+    /// True when the server was reached but the exchange with it failed
+    pub fn is_protocol_error(&self) -> bool {
+        match self {
+            PingError::Timeout(phase) | PingError::Io(phase, _) => *phase != Phase::Connect,
+            PingError::Truncated(_)
+            | PingError::MalformedVarInt
+            | PingError::MalformedString
+            | PingError::MalformedUuid(_)
+            | PingError::InvalidLength(_)
+            | PingError::UnexpectedPacketId { .. }
+            | PingError::UnknownIntent(_)
+            | PingError::OversizedFrame { .. }
+            | PingError::InvalidJson(_)
+            | PingError::PongMismatch { .. }
+            | PingError::MalformedLegacyResponse(_)
+            | PingError::MalformedBedrockResponse(_)
+            | PingError::MalformedQueryResponse(_)
+            | PingError::MalformedForgeData(_)
+            | PingError::MalformedProxyHeader(_)
+            | PingError::MalformedProxyResponse(_) => true,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn io(phase: Phase, e: io::Error) -> PingError {
+        match e.kind() {
+            io::ErrorKind::TimedOut => PingError::Timeout(phase),
+            io::ErrorKind::UnexpectedEof => PingError::Truncated(phase),
+            _ => PingError::Io(phase, e),
+        }
+    }
+}
+
+impl Display for PingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PingError::InvalidHost(host) => write!(f, "Invalid host \"{host}\""),
+            PingError::InvalidTimeout => write!(f, "Timeout in seconds must be bigger than 0"),
+            PingError::UnknownProtocol(n) => write!(f, "Unknown protocol number {n}"),
+            PingError::Resolve(host) => write!(f, "Cannot resolve \"{host}\""),
+            PingError::AddressFamily(ip, mode) => write!(f, "\"{ip}\" is not allowed in {mode:?} mode"),
+            PingError::ConnectionRefused(addr) => write!(f, "Connection to {addr} refused"),
+            PingError::Connect(addr, e) => write!(f, "Cannot connect to {addr}: {e}"),
+            PingError::Timeout(phase) => write!(f, "Timed out during {phase}"),
+            PingError::Io(phase, e) => write!(f, "I/O error during {phase}: {e}"),
+            PingError::Truncated(phase) => write!(f, "Connection closed during {phase}, response truncated"),
+            PingError::MalformedVarInt => write!(f, "Malformed response, not a valid varint"),
+            PingError::MalformedString => write!(f, "Malformed response, string is not valid UTF-8"),
+            PingError::MalformedUuid(s) => write!(f, "Invalid UUID \"{s}\""),
+            PingError::InvalidLength(len) => write!(f, "Malformed response, invalid length {len}"),
+            PingError::UnexpectedPacketId { expected, actual } => write!(f, "Unexpected packet ID {actual:#04x}, expected {expected:#04x}"),
+            PingError::UnknownIntent(n) => write!(f, "Unknown handshake intent {n}"),
+            PingError::OversizedFrame { size, max } => write!(f, "Frame of {size} bytes exceeds maximum of {max} bytes"),
+            PingError::InvalidJson(e) => write!(f, "Invalid status JSON: {e}"),
+            PingError::PongMismatch { sent, received } => write!(f, "Pong payload {received} does not match ping request {sent}"),
+            PingError::MalformedLegacyResponse(s) => write!(f, "Malformed legacy ping response \"{s}\""),
+            PingError::MalformedBedrockResponse(s) => write!(f, "Malformed Bedrock pong \"{s}\""),
+            PingError::MalformedQueryResponse(s) => write!(f, "Malformed query response \"{s}\""),
+            PingError::MalformedForgeData(s) => write!(f, "Malformed Forge mod data: {s}"),
+            PingError::MalformedProxyHeader(s) => write!(f, "Malformed PROXY protocol header: {s}"),
+            PingError::ProxyAuthFailed => write!(f, "Proxy authentication failed"),
+            PingError::InvalidProxyRequest(s) => write!(f, "Invalid proxy request, {s}"),
+            PingError::ProxyRefused(reason) => write!(f, "Proxy cannot connect to the server: {reason}"),
+            PingError::MalformedProxyResponse(s) => write!(f, "Malformed proxy response \"{s}\""),
+        }
+    }
+}
+
+impl Error for PingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PingError::Connect(_, e) | PingError::Io(_, e) => Some(e),
+            PingError::InvalidJson(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_unreachable() {
+        assert!(PingError::Resolve("nope".to_string()).is_unreachable());
+        assert!(PingError::ConnectionRefused("127.0.0.1:25565".parse().unwrap()).is_unreachable());
+        assert!(PingError::Timeout(Phase::Connect).is_unreachable());
+        assert!(!PingError::Timeout(Phase::Status).is_unreachable());
+        assert!(!PingError::MalformedVarInt.is_unreachable());
+        assert!(!PingError::UnexpectedPacketId { expected: 0, actual: 5 }.is_unreachable());
+    }
+
+    #[test]
+    fn test_is_protocol_error() {
+        assert!(PingError::Timeout(Phase::Status).is_protocol_error());
+        assert!(PingError::MalformedVarInt.is_protocol_error());
+        assert!(PingError::UnexpectedPacketId { expected: 0, actual: 5 }.is_protocol_error());
+        assert!(!PingError::Timeout(Phase::Connect).is_protocol_error());
+        assert!(!PingError::Resolve("nope".to_string()).is_protocol_error());
+        assert!(!PingError::InvalidTimeout.is_protocol_error());
+    }
+
+    #[test]
+    fn test_io_timeout() {
+        let e = PingError::io(Phase::Status, io::Error::from(io::ErrorKind::TimedOut));
+        assert!(matches!(e, PingError::Timeout(Phase::Status)));
+        let e = PingError::io(Phase::Ping, io::Error::from(io::ErrorKind::UnexpectedEof));
+        assert!(matches!(e, PingError::Truncated(Phase::Ping)));
+        assert!(e.is_protocol_error());
+        let e = PingError::io(Phase::Ping, io::Error::from(io::ErrorKind::ConnectionReset));
+        assert!(matches!(e, PingError::Io(Phase::Ping, _)));
+        assert!(e.source().is_some());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("Unexpected packet ID 0x05, expected 0x00", PingError::UnexpectedPacketId { expected: 0, actual: 5 }.to_string());
+        assert_eq!("Timed out during status", PingError::Timeout(Phase::Status).to_string());
+    }
+}
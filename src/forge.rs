@@ -0,0 +1,365 @@
+#![allow(unused)]
+
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::PingError;
+use crate::packet::{read_bool, read_string, read_u16, read_varint, write_bool, write_string, write_u16, write_varint};
+use crate::protocol::{MINECRAFT_1_13, MINECRAFT_1_18, ProtocolNum};
+use crate::status::StatusResponse;
+
+/// Version Forge sends for mods that are only required on the server
+pub const IGNORE_SERVER_ONLY: &str = "OHNOES\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}\u{1F631}";
+
+/// Client the handshake pretends to come from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClientFlavor {
+    #[default]
+    Vanilla,
+    /// Forge Mod Loader, marked in the handshake host
+    Forge,
+}
+
+impl ClientFlavor {
+    /// Suffix of the handshake host for the protocol, `\0FML\0` up to 1.12.2,
+    /// `\0FML2\0` up to 1.17.1 and `\0FML3\0` since 1.18
+    pub fn host_marker(&self, protocol: ProtocolNum) -> &'static str {
+        match self {
+            ClientFlavor::Vanilla => "",
+            ClientFlavor::Forge if protocol < MINECRAFT_1_13 => "\0FML\0",
+            ClientFlavor::Forge if protocol < MINECRAFT_1_18 => "\0FML2\0",
+            ClientFlavor::Forge => "\0FML3\0",
+        }
+    }
+}
+
+impl FromStr for ClientFlavor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vanilla" => Ok(ClientFlavor::Vanilla),
+            "forge" | "fml" => Ok(ClientFlavor::Forge),
+            _ => Err(format!("Unknown client flavor \"{s}\", expected vanilla or forge")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ForgeMod {
+    pub id: String,
+    /// `None` for mods only required on the server
+    pub version: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ForgeChannel {
+    /// Resource location, `fml:handshake`
+    pub name: String,
+    pub version: String,
+    /// Clients without the channel are refused
+    pub required: bool,
+}
+
+/// Mods and network channels of a Forge server, from `modinfo` before 1.13 and `forgeData` since
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ForgeInfo {
+    /// `FML` for `modinfo`, not sent in `forgeData`
+    pub loader: Option<String>,
+    /// FML network protocol, 2 for 1.13 to 1.17 and 3 since 1.18
+    pub network_version: Option<i32>,
+    pub mods: Vec<ForgeMod>,
+    pub channels: Vec<ForgeChannel>,
+    /// The server left out mods to keep the response small
+    pub truncated: bool,
+}
+
+impl StatusResponse {
+    /// Mod list of a Forge server, `None` for servers sending neither `modinfo` nor `forgeData`
+    pub fn forge_info(&self) -> Result<Option<ForgeInfo>, PingError> {
+        if let Some(forge_data) = &self.forge_data {
+            return parse_forge_data(forge_data).map(Some);
+        }
+        if let Some(modinfo) = &self.modinfo {
+            return parse_modinfo(modinfo).map(Some);
+        }
+        Ok(None)
+    }
+}
+
+#[derive(Deserialize)]
+struct ModInfo {
+    #[serde(rename = "type", default)]
+    loader: Option<String>,
+    #[serde(rename = "modList", default)]
+    mod_list: Vec<ModInfoEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModInfoEntry {
+    modid: String,
+    #[serde(default)]
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct ForgeData {
+    #[serde(default)]
+    channels: Vec<ForgeDataChannel>,
+    #[serde(default)]
+    mods: Vec<ForgeDataMod>,
+    #[serde(rename = "fmlNetworkVersion", default)]
+    fml_network_version: Option<i32>,
+    #[serde(default)]
+    truncated: bool,
+    /// Mods and channels packed into UTF-16 since 1.18
+    #[serde(default)]
+    d: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ForgeDataChannel {
+    res: String,
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    required: bool,
+}
+
+#[derive(Deserialize)]
+struct ForgeDataMod {
+    #[serde(rename = "modId")]
+    mod_id: String,
+    #[serde(default)]
+    modmarker: String,
+}
+
+fn mod_version(version: String) -> Option<String> {
+    Some(version).filter(|v| v != IGNORE_SERVER_ONLY)
+}
+
+/// Legacy `modinfo` of FML before 1.13
+pub fn parse_modinfo(modinfo: &Value) -> Result<ForgeInfo, PingError> {
+    let modinfo = ModInfo::deserialize(modinfo).map_err(|e| PingError::MalformedForgeData(e.to_string()))?;
+    Ok(ForgeInfo {
+        loader: modinfo.loader,
+        mods: modinfo.mod_list.into_iter().map(|v| ForgeMod { id: v.modid, version: mod_version(v.version) }).collect(),
+        ..Default::default()
+    })
+}
+
+/// `forgeData` of Forge since 1.13, including the packed `d` string of 1.18 and later
+pub fn parse_forge_data(forge_data: &Value) -> Result<ForgeInfo, PingError> {
+    let forge_data = ForgeData::deserialize(forge_data).map_err(|e| PingError::MalformedForgeData(e.to_string()))?;
+    let mut res = ForgeInfo {
+        loader: None,
+        network_version: forge_data.fml_network_version,
+        mods: forge_data.mods.into_iter().map(|v| ForgeMod { id: v.mod_id, version: mod_version(v.modmarker) }).collect(),
+        channels: forge_data.channels.into_iter().map(|v| ForgeChannel { name: v.res, version: v.version, required: v.required }).collect(),
+        truncated: forge_data.truncated,
+    };
+    if let Some(d) = forge_data.d {
+        let bytes = decode_forge_blob(&d)?;
+        read_forge_blob(&mut bytes.as_slice(), &mut res)?;
+    }
+    Ok(res)
+}
+
+/// Bytes packed in the `d` string, 15 bits per UTF-16 code unit after two units of byte length
+pub fn decode_forge_blob(d: &str) -> Result<Vec<u8>, PingError> {
+    let units: Vec<u16> = d.encode_utf16().collect();
+    if units.len() < 2 {
+        return Err(PingError::MalformedForgeData(format!("packed data of {} characters", units.len())));
+    }
+    let size = (units[0] as usize & 0x7FFF) | ((units[1] as usize & 0x7FFF) << 15);
+    // The size comes from the server, checked against the 15 bits per unit available before allocating
+    let available = (units.len() - 2) * 15 / 8;
+    if size > available {
+        return Err(PingError::MalformedForgeData(format!("packed data of {available} bytes, expected {size}")));
+    }
+    let mut res = Vec::with_capacity(size);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for unit in &units[2..] {
+        // Trailing units past the announced size are ignored
+        if res.len() == size {
+            break;
+        }
+        buffer |= (*unit as u32 & 0x7FFF) << bits;
+        bits += 15;
+        while bits >= 8 && res.len() < size {
+            res.push(buffer as u8);
+            buffer >>= 8;
+            bits -= 8;
+        }
+    }
+    if res.len() < size {
+        return Err(PingError::MalformedForgeData(format!("packed data of {} bytes, expected {size}", res.len())));
+    }
+    Ok(res)
+}
+
+/// Inverse of `decode_forge_blob`
+pub fn encode_forge_blob(bytes: &[u8]) -> String {
+    let mut units = vec![(bytes.len() & 0x7FFF) as u16, ((bytes.len() >> 15) & 0x7FFF) as u16];
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer |= (*byte as u32) << bits;
+        bits += 8;
+        if bits >= 15 {
+            units.push((buffer & 0x7FFF) as u16);
+            buffer >>= 15;
+            bits -= 15;
+        }
+    }
+    if bits > 0 {
+        units.push((buffer & 0x7FFF) as u16);
+    }
+    String::from_utf16_lossy(&units)
+}
+
+fn read_forge_blob(buf: &mut &[u8], res: &mut ForgeInfo) -> Result<(), PingError> {
+    res.truncated |= read_bool(buf)?;
+    for _ in 0..read_u16(buf)? {
+        let flags = read_varint(buf)?;
+        let id = read_string(buf)?;
+        // Lowest bit set for mods only required on the server, whose version is left out
+        let version = if flags & 1 == 0 { Some(read_string(buf)?) } else { None };
+        for _ in 0..(flags as u32 >> 1) {
+            let path = read_string(buf)?;
+            let version = read_string(buf)?;
+            let required = read_bool(buf)?;
+            res.channels.push(ForgeChannel { name: format!("{id}:{path}"), version, required });
+        }
+        res.mods.push(ForgeMod { id, version });
+    }
+    for _ in 0..read_varint(buf)? {
+        let name = read_string(buf)?;
+        let version = read_string(buf)?;
+        let required = read_bool(buf)?;
+        res.channels.push(ForgeChannel { name, version, required });
+    }
+    Ok(())
+}
+
+/// Packed `d` string for the mods, with each channel named `modid:path` attached to its mod
+/// and the other channels listed separately
+pub fn compose_forge_blob(info: &ForgeInfo) -> String {
+    let mut buf = Vec::new();
+    write_bool(&mut buf, info.truncated);
+    write_u16(&mut buf, info.mods.len() as u16);
+    let mut mod_channels = 0;
+    for forge_mod in &info.mods {
+        let prefix = format!("{}:", forge_mod.id);
+        let channels: Vec<&ForgeChannel> = info.channels.iter().filter(|v| v.name.starts_with(&prefix)).collect();
+        mod_channels += channels.len();
+        write_varint(&mut buf, ((channels.len() as i32) << 1) | forge_mod.version.is_none() as i32);
+        write_string(&mut buf, &forge_mod.id);
+        if let Some(version) = &forge_mod.version {
+            write_string(&mut buf, version);
+        }
+        for channel in channels {
+            write_string(&mut buf, &channel.name[prefix.len()..]);
+            write_string(&mut buf, &channel.version);
+            write_bool(&mut buf, channel.required);
+        }
+    }
+    let others: Vec<&ForgeChannel> = info.channels.iter()
+        .filter(|v| !info.mods.iter().any(|m| v.name.starts_with(&format!("{}:", m.id))))
+        .collect();
+    write_varint(&mut buf, others.len() as i32);
+    for channel in others {
+        write_string(&mut buf, &channel.name);
+        write_string(&mut buf, &channel.version);
+        write_bool(&mut buf, channel.required);
+    }
+    encode_forge_blob(&buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::{LATEST, MINECRAFT_1_12_2, MINECRAFT_1_17_1, MINECRAFT_1_7_10};
+
+    use super::*;
+
+    #[test]
+    fn test_host_marker() {
+        assert_eq!("", ClientFlavor::Vanilla.host_marker(LATEST));
+        assert_eq!("\0FML\0", ClientFlavor::Forge.host_marker(MINECRAFT_1_7_10));
+        assert_eq!("\0FML\0", ClientFlavor::Forge.host_marker(MINECRAFT_1_12_2));
+        assert_eq!("\0FML2\0", ClientFlavor::Forge.host_marker(MINECRAFT_1_13));
+        assert_eq!("\0FML2\0", ClientFlavor::Forge.host_marker(MINECRAFT_1_17_1));
+        assert_eq!("\0FML3\0", ClientFlavor::Forge.host_marker(MINECRAFT_1_18));
+        assert_eq!("\0FML3\0", ClientFlavor::Forge.host_marker(LATEST));
+        assert_eq!(Ok(ClientFlavor::Forge), "FML".parse());
+        assert!("fabric".parse::<ClientFlavor>().is_err());
+    }
+
+    #[test]
+    fn test_parse_modinfo() {
+        let res = StatusResponse::from_slice(br#"{"modinfo":{"type":"FML","modList":[{"modid":"mcp","version":"9.19"},{"modid":"FML","version":"8.0.99.99"}]}}"#).unwrap();
+        let info = res.forge_info().unwrap().unwrap();
+        assert_eq!(Some("FML".to_string()), info.loader);
+        assert_eq!(vec![
+            ForgeMod { id: "mcp".to_string(), version: Some("9.19".to_string()) },
+            ForgeMod { id: "FML".to_string(), version: Some("8.0.99.99".to_string()) },
+        ], info.mods);
+        assert!(info.channels.is_empty());
+
+        let res = StatusResponse::from_slice(br#"{"description":"A Minecraft Server"}"#).unwrap();
+        assert!(res.forge_info().unwrap().is_none());
+        let res = StatusResponse::from_slice(br#"{"modinfo":{"modList":"none"}}"#).unwrap();
+        assert!(matches!(res.forge_info(), Err(PingError::MalformedForgeData(_))));
+    }
+
+    #[test]
+    fn test_parse_forge_data() {
+        let raw = format!(r#"{{"forgeData":{{"channels":[{{"res":"fml:handshake","version":"FML2","required":true}}],
+            "mods":[{{"modId":"forge","modmarker":"36.2.39"}},{{"modId":"servercore","modmarker":"{IGNORE_SERVER_ONLY}"}}],"fmlNetworkVersion":2}}}}"#);
+        let res = StatusResponse::from_slice(raw.as_bytes()).unwrap();
+        let info = res.forge_info().unwrap().unwrap();
+        assert_eq!(None, info.loader);
+        assert_eq!(Some(2), info.network_version);
+        assert_eq!(vec![
+            ForgeMod { id: "forge".to_string(), version: Some("36.2.39".to_string()) },
+            ForgeMod { id: "servercore".to_string(), version: None },
+        ], info.mods);
+        assert_eq!(vec![ForgeChannel { name: "fml:handshake".to_string(), version: "FML2".to_string(), required: true }], info.channels);
+        assert!(!info.truncated);
+    }
+
+    #[test]
+    fn test_forge_blob() {
+        for len in 0..40 {
+            let bytes: Vec<u8> = (0..len).map(|v| (v * 37 + 11) as u8).collect();
+            assert_eq!(bytes, decode_forge_blob(&encode_forge_blob(&bytes)).unwrap());
+        }
+        assert!(decode_forge_blob("").is_err());
+        // Announces 3 bytes but carries 15 bits
+        assert!(decode_forge_blob("\u{3}\u{0}\u{1}").is_err());
+        let surplus = format!("{}{}", encode_forge_blob(&[1, 2, 3]), "\u{7FFF}".repeat(8));
+        assert_eq!(vec![1, 2, 3], decode_forge_blob(&surplus).unwrap());
+        // Announces 2^30 - 1 bytes with a short body
+        assert!(matches!(decode_forge_blob("\u{7FFF}\u{7FFF}\u{1}\u{2}"), Err(PingError::MalformedForgeData(_))));
+
+        let info = ForgeInfo {
+            loader: None,
+            network_version: Some(3),
+            mods: vec![
+                ForgeMod { id: "forge".to_string(), version: Some("47.2.0".to_string()) },
+                ForgeMod { id: "servercore".to_string(), version: None },
+            ],
+            channels: vec![
+                ForgeChannel { name: "forge:split".to_string(), version: "1.1".to_string(), required: true },
+                ForgeChannel { name: "minecraft:register".to_string(), version: "FML3".to_string(), required: false },
+            ],
+            truncated: true,
+        };
+        let forge_data = serde_json::json!({"channels": [], "mods": [], "truncated": false, "fmlNetworkVersion": 3, "d": compose_forge_blob(&info)});
+        let res = StatusResponse { forge_data: Some(forge_data), ..Default::default() };
+        assert_eq!(Some(info), res.forge_info().unwrap());
+    }
+}
@@ -0,0 +1,271 @@
+#![allow(unused)]
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::error::{Phase, PingError};
+
+/// First bytes of every v2 header
+pub const PROXY_V2_SIGNATURE: [u8; 12] = [0x0D, 0x0A, 0x0D, 0x0A, 0x00, 0x0D, 0x0A, 0x51, 0x55, 0x49, 0x54, 0x0A];
+
+/// Longest v1 header, line ending included
+const MAX_V1_HEADER_SIZE: usize = 107;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProxyVersion {
+    /// Human readable line, `PROXY TCP4 198.51.100.7 203.0.113.1 51234 25565\r\n`
+    V1,
+    /// Binary header, with optional TLVs
+    #[default]
+    V2,
+}
+
+impl FromStr for ProxyVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "1" | "v1" => Ok(ProxyVersion::V1),
+            "2" | "v2" => Ok(ProxyVersion::V2),
+            _ => Err(format!("Unknown PROXY protocol version \"{s}\", expected v1 or v2")),
+        }
+    }
+}
+
+/// Type-length-value extension of a v2 header, such as the authority (0x02) the client connected to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProxyTlv {
+    pub kind: u8,
+    pub value: Vec<u8>,
+}
+
+/// PROXY protocol header sent before the handshake, for servers behind HAProxy or TCPShield with `proxy-protocol: true`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProxyHeader {
+    pub version: ProxyVersion,
+    /// Client address announced, the local address of the connection when `None`
+    pub source: Option<SocketAddr>,
+    /// Server address announced, the server connected to when `None`.
+    /// Required through a proxy resolving the host, as the server address is then unknown.
+    pub destination: Option<SocketAddr>,
+    /// Only sent with v2
+    pub tlvs: Vec<ProxyTlv>,
+}
+
+impl ProxyHeader {
+    /// Header for a connection from `local` to `peer`, unless addresses are given
+    pub fn compose(&self, local: SocketAddr, peer: SocketAddr) -> Vec<u8> {
+        let source = self.source.unwrap_or(local);
+        let destination = self.destination.unwrap_or(peer);
+        match self.version {
+            ProxyVersion::V1 => compose_proxy_header_v1(source, destination),
+            ProxyVersion::V2 => compose_proxy_header_v2(source, destination, &self.tlvs),
+        }
+    }
+}
+
+/// Addresses of the same family, IPv4 ones mapped to IPv6 if the other is IPv6
+fn same_family(source: SocketAddr, destination: SocketAddr) -> (SocketAddr, SocketAddr) {
+    let to_v6 = |v: SocketAddr| match v.ip() {
+        IpAddr::V4(ip) => SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), v.port()),
+        IpAddr::V6(_) => v,
+    };
+    if source.is_ipv4() == destination.is_ipv4() {
+        (source, destination)
+    } else {
+        (to_v6(source), to_v6(destination))
+    }
+}
+
+pub fn compose_proxy_header_v1(source: SocketAddr, destination: SocketAddr) -> Vec<u8> {
+    let (source, destination) = same_family(source, destination);
+    let family = if source.is_ipv4() { "TCP4" } else { "TCP6" };
+    format!("PROXY {family} {} {} {} {}\r\n", source.ip(), destination.ip(), source.port(), destination.port()).into_bytes()
+}
+
+pub fn compose_proxy_header_v2(source: SocketAddr, destination: SocketAddr, tlvs: &[ProxyTlv]) -> Vec<u8> {
+    let (source, destination) = same_family(source, destination);
+    let mut addresses = Vec::new();
+    let family = match (source.ip(), destination.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            addresses.extend_from_slice(&src.octets());
+            addresses.extend_from_slice(&dst.octets());
+            0x11
+        }
+        (src, dst) => {
+            addresses.extend_from_slice(&to_ipv6(src).octets());
+            addresses.extend_from_slice(&to_ipv6(dst).octets());
+            0x21
+        }
+    };
+    addresses.extend_from_slice(&source.port().to_be_bytes());
+    addresses.extend_from_slice(&destination.port().to_be_bytes());
+    for tlv in tlvs {
+        addresses.push(tlv.kind);
+        addresses.extend_from_slice(&(tlv.value.len() as u16).to_be_bytes());
+        addresses.extend_from_slice(&tlv.value);
+    }
+
+    let mut res = PROXY_V2_SIGNATURE.to_vec();
+    // Version 2, PROXY command
+    res.push(0x21);
+    res.push(family);
+    res.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+    res.extend_from_slice(&addresses);
+    res
+}
+
+fn to_ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(v) => v.to_ipv6_mapped(),
+        IpAddr::V6(v) => v,
+    }
+}
+
+/// Reads a v1 or v2 header, as servers with `proxy-protocol: true` do before the handshake.
+/// Addresses are `None` for `UNKNOWN` and `LOCAL` headers.
+pub async fn read_proxy_header<R: AsyncRead + Unpin>(stream: &mut R) -> Result<ProxyHeader, PingError> {
+    let phase = Phase::Handshake;
+    let mut prefix = [0u8; 12];
+    stream.read_exact(&mut prefix[..5]).await.map_err(|e| PingError::io(phase, e))?;
+    if &prefix[..5] == b"PROXY" {
+        let mut line = prefix[..5].to_vec();
+        while !line.ends_with(b"\r\n") {
+            if line.len() >= MAX_V1_HEADER_SIZE {
+                return Err(PingError::MalformedProxyHeader("v1 header without line ending".to_string()));
+            }
+            line.push(stream.read_u8().await.map_err(|e| PingError::io(phase, e))?);
+        }
+        return parse_proxy_header_v1(&String::from_utf8_lossy(&line[..line.len() - 2]));
+    }
+
+    stream.read_exact(&mut prefix[5..]).await.map_err(|e| PingError::io(phase, e))?;
+    if prefix != PROXY_V2_SIGNATURE {
+        return Err(PingError::MalformedProxyHeader("missing signature".to_string()));
+    }
+    let mut fixed = [0u8; 4];
+    stream.read_exact(&mut fixed).await.map_err(|e| PingError::io(phase, e))?;
+    let mut body = vec![0u8; u16::from_be_bytes([fixed[2], fixed[3]]) as usize];
+    stream.read_exact(&mut body).await.map_err(|e| PingError::io(phase, e))?;
+    parse_proxy_header_v2(fixed[0], fixed[1], &body)
+}
+
+fn parse_proxy_header_v1(line: &str) -> Result<ProxyHeader, PingError> {
+    let malformed = || PingError::MalformedProxyHeader(line.to_string());
+    let parts: Vec<&str> = line.split(' ').collect();
+    match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(ProxyHeader { version: ProxyVersion::V1, ..Default::default() }),
+        ["PROXY", "TCP4" | "TCP6", src, dst, sport, dport] => {
+            let address = |ip: &str, port: &str| -> Option<SocketAddr> { Some(SocketAddr::new(ip.parse().ok()?, port.parse().ok()?)) };
+            Ok(ProxyHeader {
+                version: ProxyVersion::V1,
+                source: Some(address(src, sport).ok_or_else(malformed)?),
+                destination: Some(address(dst, dport).ok_or_else(malformed)?),
+                tlvs: Vec::new(),
+            })
+        }
+        _ => Err(malformed()),
+    }
+}
+
+fn parse_proxy_header_v2(version_command: u8, family: u8, body: &[u8]) -> Result<ProxyHeader, PingError> {
+    if version_command >> 4 != 2 {
+        return Err(PingError::MalformedProxyHeader(format!("version {}", version_command >> 4)));
+    }
+    let mut res = ProxyHeader { version: ProxyVersion::V2, ..Default::default() };
+    // LOCAL command, health checks of the proxy itself
+    if version_command & 0x0F == 0 {
+        return Ok(res);
+    }
+    let address_len = match family {
+        0x11 => 12,
+        0x21 => 36,
+        _ => return Err(PingError::MalformedProxyHeader(format!("address family {family:#04x}"))),
+    };
+    if body.len() < address_len {
+        return Err(PingError::MalformedProxyHeader(format!("addresses of {} bytes", body.len())));
+    }
+    let (addresses, mut tlvs) = body.split_at(address_len);
+    let ip_len = (address_len - 4) / 2;
+    let ip = |bytes: &[u8]| -> IpAddr {
+        match <[u8; 4]>::try_from(bytes) {
+            Ok(v) => IpAddr::from(v),
+            Err(_) => IpAddr::from(<[u8; 16]>::try_from(bytes).unwrap_or_default()),
+        }
+    };
+    let ports = &addresses[ip_len * 2..];
+    res.source = Some(SocketAddr::new(ip(&addresses[..ip_len]), u16::from_be_bytes([ports[0], ports[1]])));
+    res.destination = Some(SocketAddr::new(ip(&addresses[ip_len..ip_len * 2]), u16::from_be_bytes([ports[2], ports[3]])));
+
+    while !tlvs.is_empty() {
+        if tlvs.len() < 3 {
+            return Err(PingError::MalformedProxyHeader("truncated TLV".to_string()));
+        }
+        let len = u16::from_be_bytes([tlvs[1], tlvs[2]]) as usize;
+        let Some(value) = tlvs.get(3..3 + len) else {
+            return Err(PingError::MalformedProxyHeader("truncated TLV".to_string()));
+        };
+        res.tlvs.push(ProxyTlv { kind: tlvs[0], value: value.to_vec() });
+        tlvs = &tlvs[3 + len..];
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_proxy_header_v1() {
+        let source: SocketAddr = "198.51.100.7:51234".parse().unwrap();
+        let destination: SocketAddr = "203.0.113.1:25565".parse().unwrap();
+        assert_eq!(b"PROXY TCP4 198.51.100.7 203.0.113.1 51234 25565\r\n".to_vec(), compose_proxy_header_v1(source, destination));
+        let destination: SocketAddr = "[2001:db8::1]:25565".parse().unwrap();
+        assert_eq!(b"PROXY TCP6 ::ffff:198.51.100.7 2001:db8::1 51234 25565\r\n".to_vec(), compose_proxy_header_v1(source, destination));
+    }
+
+    #[test]
+    fn test_compose_proxy_header_v2() {
+        let source: SocketAddr = "198.51.100.7:51234".parse().unwrap();
+        let destination: SocketAddr = "203.0.113.1:25565".parse().unwrap();
+        let mut expected = PROXY_V2_SIGNATURE.to_vec();
+        expected.extend_from_slice(&[0x21, 0x11, 0x00, 0x12, 198, 51, 100, 7, 203, 0, 113, 1, 0xC8, 0x22, 0x63, 0xDD, 0x02, 0x00, 0x03]);
+        expected.extend_from_slice(b"mc.");
+        assert_eq!(expected, compose_proxy_header_v2(source, destination, &[ProxyTlv { kind: 0x02, value: b"mc.".to_vec() }]));
+
+        let source: SocketAddr = "[2001:db8::7]:51234".parse().unwrap();
+        let res = compose_proxy_header_v2(source, destination, &[]);
+        assert_eq!([0x21, 0x21, 0x00, 0x24], res[12..16]);
+        assert_eq!(52, res.len());
+    }
+
+    #[tokio::test]
+    async fn test_read_proxy_header() {
+        let header = ProxyHeader {
+            version: ProxyVersion::V1,
+            source: Some("[2001:db8::7]:51234".parse().unwrap()),
+            destination: Some("[2001:db8::1]:25565".parse().unwrap()),
+            tlvs: Vec::new(),
+        };
+        let raw = header.compose("127.0.0.1:1".parse().unwrap(), "127.0.0.1:2".parse().unwrap());
+        assert_eq!(header, read_proxy_header(&mut raw.as_slice()).await.unwrap());
+
+        let header = ProxyHeader { version: ProxyVersion::V2, tlvs: vec![ProxyTlv { kind: 0xE0, value: vec![1, 2] }], ..header };
+        let raw = header.compose("127.0.0.1:1".parse().unwrap(), "127.0.0.1:2".parse().unwrap());
+        assert_eq!(header, read_proxy_header(&mut raw.as_slice()).await.unwrap());
+
+        let header = ProxyHeader { source: None, destination: None, tlvs: Vec::new(), ..header };
+        let raw = header.compose("127.0.0.1:51234".parse().unwrap(), "127.0.0.1:25565".parse().unwrap());
+        let res = read_proxy_header(&mut raw.as_slice()).await.unwrap();
+        assert_eq!(Some("127.0.0.1:51234".parse().unwrap()), res.source);
+        assert_eq!(Some("127.0.0.1:25565".parse().unwrap()), res.destination);
+
+        let res = read_proxy_header(&mut b"PROXY UNKNOWN\r\n".as_slice()).await.unwrap();
+        assert_eq!(None, res.source);
+        assert!(matches!(read_proxy_header(&mut b"PROXY TCP4 nowhere\r\n".as_slice()).await, Err(PingError::MalformedProxyHeader(_))));
+        assert!(matches!(read_proxy_header(&mut [0x10, 0x00, 0xFF, 0x05, 0x09, 0x6C, 0x6F, 0x63, 0x61, 0x6C, 0x68, 0x6F].as_slice()).await,
+            Err(PingError::MalformedProxyHeader(_))));
+    }
+}
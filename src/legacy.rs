@@ -0,0 +1,221 @@
+#![allow(unused)]
+
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::error::{Phase, PingError};
+use crate::protocol::{handshake_host, open_stream, PingOptions, port_or_default, ProtocolNum, shutoff, with_timeout};
+use crate::status::{StatusPlayers, StatusResponse, StatusResult, StatusVersion};
+
+/// Protocol version sent in the 1.6 MC|PingHost plugin message, 1.6.4
+pub const LEGACY_PROTOCOL_1_6: u8 = 78;
+
+const PING_HOST_CHANNEL: &str = "MC|PingHost";
+
+/// Server list ping used before the netty rewrite in 1.7
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LegacyVariant {
+    /// Beta 1.8 to 1.3, a single 0xFE
+    Beta1_8,
+    /// 1.4 and 1.5, 0xFE 0x01
+    V1_4,
+    /// 1.6, 0xFE 0x01 followed by a MC|PingHost plugin message
+    V1_6,
+}
+
+impl FromStr for LegacyVariant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "beta1.8" | "b1.8" | "1.3" => Ok(LegacyVariant::Beta1_8),
+            "1.4" | "1.5" => Ok(LegacyVariant::V1_4),
+            "1.6" => Ok(LegacyVariant::V1_6),
+            _ => Err(format!("Unknown legacy ping variant \"{s}\", expected beta1.8, 1.4 or 1.6")),
+        }
+    }
+}
+
+fn push_utf16be(content: &mut Vec<u8>, s: &str) {
+    s.encode_utf16().for_each(|c| content.extend_from_slice(&c.to_be_bytes()));
+}
+
+pub fn compose_legacy_ping_packet(variant: LegacyVariant, host: &str, port: u16) -> Vec<u8> {
+    match variant {
+        LegacyVariant::Beta1_8 => vec![0xFE],
+        LegacyVariant::V1_4 => vec![0xFE, 0x01],
+        LegacyVariant::V1_6 => {
+            let host_strlen = host.encode_utf16().count();
+            let mut content: Vec<u8> = vec![0xFE, 0x01, 0xFA];
+            content.extend_from_slice(&(PING_HOST_CHANNEL.len() as u16).to_be_bytes());
+            push_utf16be(&mut content, PING_HOST_CHANNEL);
+            // Protocol version. Host string length. Host. Port as int.
+            content.extend_from_slice(&(7 + 2 * host_strlen as u16).to_be_bytes());
+            content.push(LEGACY_PROTOCOL_1_6);
+            content.extend_from_slice(&(host_strlen as u16).to_be_bytes());
+            push_utf16be(&mut content, host);
+            content.extend_from_slice(&(port as i32).to_be_bytes());
+            content
+        }
+    }
+}
+
+/// Parses the reason string of the 0xFF kick packet.
+/// 1.4 and later send `§1\0protocol\0version\0motd\0online\0max`,
+/// older servers send `motd§online§max`.
+pub fn parse_legacy_kick(reason: &str) -> Result<StatusResponse, PingError> {
+    let malformed = || PingError::MalformedLegacyResponse(reason.to_string());
+    let (version, motd, online, max) = if let Some(rest) = reason.strip_prefix("§1\0") {
+        let fields: Vec<&str> = rest.split('\0').collect();
+        if fields.len() != 5 {
+            return Err(malformed());
+        }
+        let protocol = ProtocolNum::from_str(fields[0]).map_err(|_| malformed())?;
+        let version = StatusVersion { name: fields[1].to_string(), protocol };
+        (Some(version), fields[2], fields[3], fields[4])
+    } else {
+        let mut fields = reason.rsplitn(3, '§');
+        let max = fields.next().ok_or_else(malformed)?;
+        let online = fields.next().ok_or_else(malformed)?;
+        let motd = fields.next().ok_or_else(malformed)?;
+        (None, motd, online, max)
+    };
+    let online = i64::from_str(online).map_err(|_| malformed())?;
+    let max = i64::from_str(max).map_err(|_| malformed())?;
+    Ok(StatusResponse {
+        version,
+        players: Some(StatusPlayers { max, online, sample: Vec::new() }),
+        description: Some(Value::String(motd.to_string())),
+        ..Default::default()
+    })
+}
+
+pub async fn legacy_ping(host: &str, port: u16, variant: LegacyVariant, options: &PingOptions) -> Result<StatusResult, PingError> {
+    let host_touse = handshake_host(host, options);
+    let port_touse = port_or_default(port);
+    let mut stream = open_stream(host, port_touse, options).await?;
+    let res = legacy_exchange_on(&mut stream, options.timeout_duration(), variant, &host_touse, port_touse).await;
+    shutoff(&mut stream).await;
+    let (raw, reason, latency) = res?;
+    let response = parse_legacy_kick(&reason)?;
+    let advertised_protocol = response.version.as_ref().map(|v| v.protocol);
+    Ok(StatusResult { response, raw, latency: Some(latency), legacy: Some(variant), requested_protocol: None, advertised_protocol })
+}
+
+async fn legacy_exchange_on(stream: &mut TcpStream, dur: Duration, variant: LegacyVariant, host: &str, port: u16) -> Result<(Vec<u8>, String, Duration), PingError> {
+    let sent_at = Instant::now();
+    with_timeout(dur, Phase::Handshake, stream.write_all(&compose_legacy_ping_packet(variant, host, port))).await?;
+
+    let byte = with_timeout(dur, Phase::Status, stream.read_u8()).await?;
+    if byte != 0xFF {
+        return Err(PingError::UnexpectedPacketId { expected: 0xFF, actual: byte as i32 });
+    }
+    let strlen = with_timeout(dur, Phase::Status, stream.read_u16()).await?;
+    let mut res = vec![0u8; strlen as usize * 2];
+    with_timeout(dur, Phase::Status, stream.read_exact(&mut res)).await?;
+    let latency = sent_at.elapsed();
+
+    let units: Vec<u16> = res.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+    let reason = String::from_utf16_lossy(&units);
+
+    let mut raw = Vec::with_capacity(res.len() + 3);
+    raw.push(byte);
+    raw.extend_from_slice(&strlen.to_be_bytes());
+    raw.append(&mut res);
+    Ok((raw, reason, latency))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use crate::protocol::ping_with_options;
+
+    use super::*;
+
+    async fn spawn_legacy_server(reason: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut conn, _)) = listener.accept().await {
+                let mut buf = [0u8; 512];
+                let _ = conn.read(&mut buf).await;
+                let mut kick = vec![0xFF];
+                kick.extend_from_slice(&(reason.encode_utf16().count() as u16).to_be_bytes());
+                push_utf16be(&mut kick, reason);
+                let _ = conn.write_all(&kick).await;
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_legacy_ping() {
+        let port = spawn_legacy_server("§1\x0078\x001.6.4\x00Legacy\x001\x0010").await;
+
+        let options = PingOptions { legacy: Some(LegacyVariant::V1_6), timeout: 2, ..Default::default() };
+        let res = ping_with_options("127.0.0.1", port, &options).await;
+        assert!(res.is_ok());
+        let res = res.unwrap();
+        assert_eq!(Some(LegacyVariant::V1_6), res.legacy);
+        assert_eq!(None, res.requested_protocol);
+        assert_eq!(Some(78), res.advertised_protocol);
+        assert_eq!("Legacy", res.response.description_text());
+        assert_eq!(78, res.response.version.unwrap().protocol);
+
+        let options = PingOptions { timeout: 2, ..Default::default() };
+        assert!(ping_with_options("127.0.0.1", port, &options).await.is_err());
+
+        let options = PingOptions { legacy_fallback: true, timeout: 2, ..Default::default() };
+        let res = ping_with_options("127.0.0.1", port, &options).await;
+        assert!(res.is_ok());
+        assert_eq!(Some(LegacyVariant::V1_6), res.unwrap().legacy);
+    }
+
+    #[test]
+    fn test_compose_legacy_ping_packet() {
+        assert_eq!(vec![0xFE], compose_legacy_ping_packet(LegacyVariant::Beta1_8, "localhost", 25565));
+        assert_eq!(vec![0xFE, 0x01], compose_legacy_ping_packet(LegacyVariant::V1_4, "localhost", 25565));
+
+        let mut e: Vec<u8> = vec![0xFE, 0x01, 0xFA, 0x00, 0x0B];
+        "MC|PingHost".bytes().for_each(|b| e.extend_from_slice(&[0x00, b]));
+        e.extend_from_slice(&[0x00, 0x19, 78, 0x00, 0x09]);
+        "localhost".bytes().for_each(|b| e.extend_from_slice(&[0x00, b]));
+        e.extend_from_slice(&[0x00, 0x00, 0x63, 0xDD]);
+        assert_eq!(e, compose_legacy_ping_packet(LegacyVariant::V1_6, "localhost", 25565));
+    }
+
+    #[test]
+    fn test_parse_legacy_kick() {
+        let res = parse_legacy_kick("§1\x00127\x001.6.4\x00A Minecraft Server\x005\x0020");
+        assert!(res.is_ok());
+        let res = res.unwrap();
+        assert_eq!(Some(StatusVersion { name: "1.6.4".to_string(), protocol: 127 }), res.version);
+        assert_eq!(Some(StatusPlayers { max: 20, online: 5, sample: Vec::new() }), res.players);
+        assert_eq!("A Minecraft Server", res.description_text());
+
+        let res = parse_legacy_kick("A §aBeta§r Server§3§10");
+        assert!(res.is_ok());
+        let res = res.unwrap();
+        assert!(res.version.is_none());
+        assert_eq!(Some(StatusPlayers { max: 10, online: 3, sample: Vec::new() }), res.players);
+        assert_eq!("A §aBeta§r Server", res.description_text());
+
+        assert!(parse_legacy_kick("").is_err());
+        assert!(parse_legacy_kick("§1\x00127\x001.6.4\x00motd\x005").is_err());
+        assert!(parse_legacy_kick("§1\x00x\x001.6.4\x00motd\x005\x0020").is_err());
+        assert!(parse_legacy_kick("motd§a§20").is_err());
+    }
+
+    #[test]
+    fn test_legacy_variant_from_str() {
+        assert_eq!(Ok(LegacyVariant::Beta1_8), LegacyVariant::from_str("beta1.8"));
+        assert_eq!(Ok(LegacyVariant::V1_4), LegacyVariant::from_str("1.5"));
+        assert_eq!(Ok(LegacyVariant::V1_6), LegacyVariant::from_str("1.6"));
+        assert!(LegacyVariant::from_str("1.7").is_err());
+    }
+}
@@ -1,9 +1,104 @@
+pub use bedrock::BEDROCK_DEFAULT_PORT;
+pub use bedrock::bedrock_ping;
+pub use bedrock::BedrockResult;
+pub use bedrock::BedrockStatus;
+pub use bedrock::compose_unconnected_ping_packet;
+pub use bedrock::parse_server_id;
+pub use bedrock::parse_unconnected_pong;
+pub use dns::IpMode;
 pub use dns::resolve;
+pub use dns::split_host_port;
+pub use error::Phase;
+pub use error::PingError;
+pub use forge::ClientFlavor;
+pub use forge::compose_forge_blob;
+pub use forge::decode_forge_blob;
+pub use forge::encode_forge_blob;
+pub use forge::ForgeChannel;
+pub use forge::ForgeInfo;
+pub use forge::ForgeMod;
+pub use forge::IGNORE_SERVER_ONLY;
+pub use forge::parse_forge_data;
+pub use forge::parse_modinfo;
+pub use haproxy::compose_proxy_header_v1;
+pub use haproxy::compose_proxy_header_v2;
+pub use haproxy::PROXY_V2_SIGNATURE;
+pub use haproxy::ProxyHeader;
+pub use haproxy::ProxyTlv;
+pub use haproxy::ProxyVersion;
+pub use haproxy::read_proxy_header;
+pub use legacy::compose_legacy_ping_packet;
+pub use legacy::legacy_ping;
+pub use legacy::LegacyVariant;
+pub use legacy::parse_legacy_kick;
+pub use login::accepts_transfers;
+pub use login::login_probe;
+pub use login::LoginOptions;
+pub use login::LoginProbe;
+pub use login::LoginReply;
+pub use login::PROBE_USERNAME;
+pub use login::support_matrix;
+pub use login::SupportEntry;
+pub use packet::BungeeForwarding;
 pub use packet::compose_handshake_packet;
+pub use packet::compose_login_disconnect_packet;
+pub use packet::compose_login_start_packet;
+pub use packet::compose_ping_request_packet;
+pub use packet::compose_pong_response_packet;
 pub use packet::compose_status_request_packet;
+pub use packet::compose_status_response_packet;
+pub use packet::decode_packet;
+pub use packet::decode_versioned_packet;
+pub use packet::encode_packet;
+pub use packet::encode_versioned_packet;
+pub use packet::EncryptionRequest;
+pub use packet::format_uuid;
+pub use packet::Handshake;
+pub use packet::Intent;
+pub use packet::LoginDisconnect;
+pub use packet::LoginPluginRequest;
+pub use packet::LoginProperty;
+pub use packet::LoginStart;
+pub use packet::LoginSuccess;
+pub use packet::Packet;
+pub use packet::parse_handshake_packet;
+pub use packet::parse_status_response_packet;
+pub use packet::parse_uuid;
+pub use packet::PingRequest;
+pub use packet::PongResponse;
+pub use packet::Position;
+pub use packet::read_bool;
+pub use packet::read_byte_array;
+pub use packet::read_frame;
+pub use packet::read_i64;
+pub use packet::read_packet;
+pub use packet::read_position;
+pub use packet::read_string;
+pub use packet::read_u16;
+pub use packet::read_uuid;
+pub use packet::read_varint;
+pub use packet::read_varlong;
+pub use packet::SetCompression;
+pub use packet::StatusRequest;
+pub use packet::StatusResponsePacket;
+pub use packet::VersionedPacket;
+pub use packet::write_bool;
+pub use packet::write_byte_array;
+pub use packet::write_i64;
+pub use packet::write_packet;
+pub use packet::write_position;
+pub use packet::write_string;
+pub use packet::write_u16;
+pub use packet::write_uuid;
+pub use packet::write_varint;
+pub use packet::write_varlong;
+pub use packet::write_versioned_packet;
+pub use protocol::DEFAULT_MAX_RESPONSE_SIZE;
 pub use protocol::DEFAULT_PORT;
 pub use protocol::is_known_protocol_number;
+pub use protocol::is_snapshot_protocol_number;
 pub use protocol::LATEST;
+pub use protocol::MAX_SNAPSHOT_NUMBER;
 pub use protocol::MINECRAFT_1_10;
 pub use protocol::MINECRAFT_1_10_1;
 pub use protocol::MINECRAFT_1_10_2;
@@ -83,8 +178,61 @@ pub use protocol::MINECRAFT_1_9_2;
 pub use protocol::MINECRAFT_1_9_3;
 pub use protocol::MINECRAFT_1_9_4;
 pub use protocol::ping;
+pub use protocol::ping_status;
+pub use protocol::ping_with_options;
+pub use protocol::PingOptions;
 pub use protocol::ProtocolNum;
+pub use protocol::snapshot_protocol;
+pub use protocol::SNAPSHOT_PROTOCOL_BIT;
+pub use proxy::HTTP_PROXY_DEFAULT_PORT;
+pub use proxy::Proxy;
+pub use proxy::ProxyKind;
+pub use proxy::SOCKS5_DEFAULT_PORT;
+pub use query::compose_basic_stat_packet;
+pub use query::compose_full_stat_packet;
+pub use query::compose_query_handshake_packet;
+pub use query::parse_basic_stat;
+pub use query::parse_full_stat;
+pub use query::parse_handshake_response;
+pub use query::query_basic;
+pub use query::query_full;
+pub use query::QueryBasicStat;
+pub use query::QueryFullStat;
+pub use rcon::compose_rcon_packet;
+pub use rcon::RCON_DEFAULT_PORT;
+pub use rcon::RconClient;
+pub use rcon::RconError;
+pub use rcon::RconOptions;
+pub use rcon::RconPacket;
+pub use rcon::read_rcon_packet;
+pub use server::favicon_from_png;
+pub use server::ServerOptions;
+pub use server::StatusServer;
+pub use status::StatusPlayers;
+pub use status::StatusPlayerSample;
+pub use status::StatusResponse;
+pub use status::StatusResult;
+pub use status::StatusVersion;
+pub use version::GameVersion;
+pub use version::parse_protocol;
+pub use version::ProtocolKind;
+pub use version::ReleaseType;
+pub use version::VersionRegistry;
 
 pub mod packet;
+pub mod bedrock;
 pub mod dns;
+pub mod error;
+pub mod forge;
+pub mod haproxy;
+pub mod legacy;
+pub mod login;
 pub mod protocol;
+pub mod proxy;
+pub mod query;
+pub mod rcon;
+pub mod server;
+pub mod status;
+pub mod version;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
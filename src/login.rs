@@ -0,0 +1,411 @@
+#![allow(unused)]
+
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::net::TcpStream;
+
+use crate::error::{Phase, PingError};
+use crate::packet::*;
+use crate::protocol::{handshake_host, open_stream, PingOptions, port_or_default, ProtocolNum, shutoff, with_timeout};
+use crate::status::chat_text;
+use crate::version::VersionRegistry;
+
+/// Player name sent in Login Start by the probes
+pub const PROBE_USERNAME: &str = "RollingGlass";
+
+/// Translation key of the disconnect message of servers with `accepts-transfers=false`
+const TRANSFERS_DISABLED_KEY: &str = "multiplayer.disconnect.transfers_disabled";
+
+/// Translation keys of the vanilla disconnect messages for mismatched versions
+const VERSION_MISMATCH_KEYS: &[&str] = &[
+    "multiplayer.disconnect.outdated_client",
+    "multiplayer.disconnect.outdated_server",
+    "multiplayer.disconnect.incompatible",
+];
+
+/// First packet a server answers Login Start with
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoginReply {
+    /// Online mode server accepting the client version
+    EncryptionRequest(EncryptionRequest),
+    /// Offline mode server accepting the client
+    LoginSuccess(LoginSuccess),
+    /// Compression threshold, sent before Login Success by servers accepting the client
+    SetCompression(i32),
+    /// Modded server or proxy negotiating over this channel, the version check is already passed
+    PluginRequest(String),
+    /// Login Disconnect with its JSON chat component
+    Disconnect(String),
+}
+
+impl LoginReply {
+    /// True when the server went on with the login
+    pub fn is_accepted(&self) -> bool {
+        !matches!(self, LoginReply::Disconnect(_))
+    }
+
+    /// Reason of a Login Disconnect as plain text, the raw reason if it is not a chat component
+    pub fn disconnect_text(&self) -> Option<String> {
+        match self {
+            LoginReply::Disconnect(reason) => match serde_json::from_str::<Value>(reason) {
+                Ok(component) => Some(chat_text(&component)),
+                Err(_) => Some(reason.clone()),
+            },
+            _ => None,
+        }
+    }
+
+    /// Decodes a clientbound login packet, `content` starts at the packet ID
+    pub fn decode(protocol: ProtocolNum, content: &[u8]) -> Result<LoginReply, PingError> {
+        let mut buf = content;
+        match read_varint(&mut buf)? {
+            LoginDisconnect::ID => Ok(LoginReply::Disconnect(decode_packet::<LoginDisconnect>(content)?.reason)),
+            EncryptionRequest::ID => Ok(LoginReply::EncryptionRequest(decode_versioned_packet(protocol, content)?)),
+            LoginSuccess::ID => Ok(LoginReply::LoginSuccess(decode_versioned_packet(protocol, content)?)),
+            SetCompression::ID => Ok(LoginReply::SetCompression(decode_packet::<SetCompression>(content)?.threshold)),
+            LoginPluginRequest::ID => Ok(LoginReply::PluginRequest(decode_packet::<LoginPluginRequest>(content)?.channel)),
+            id => Err(PingError::UnexpectedPacketId { expected: LoginDisconnect::ID, actual: id }),
+        }
+    }
+
+    /// True for a disconnect refusing the client version, `Outdated client!` or `Outdated server!` and their translations
+    pub fn is_version_mismatch(&self) -> bool {
+        let translated = self.translation_key().is_some_and(|v| VERSION_MISMATCH_KEYS.contains(&v.as_str()));
+        translated || self.disconnect_text().is_some_and(|v| v.to_lowercase().contains("outdated"))
+    }
+
+    /// True for the disconnect of a server refusing transferred players
+    pub fn is_transfer_refusal(&self) -> bool {
+        self.translation_key().is_some_and(|v| v == TRANSFERS_DISABLED_KEY)
+    }
+
+    /// Translation key of a Login Disconnect reason
+    fn translation_key(&self) -> Option<String> {
+        let LoginReply::Disconnect(reason) = self else {
+            return None;
+        };
+        let component = serde_json::from_str::<Value>(reason).ok()?;
+        component.get("translate")?.as_str().map(|v| v.to_string())
+    }
+}
+
+/// Sends a handshake with next state Login and Login Start, then reads the first answer
+pub(crate) async fn login_exchange(host: &str, port: u16, options: &PingOptions, start: &LoginStart) -> Result<LoginReply, PingError> {
+    options.check_protocol()?;
+    let host_touse = handshake_host(host, options);
+    let port_touse = port_or_default(port);
+    let mut stream = open_stream(host, port_touse, options).await?;
+    let res = login_exchange_on(&mut stream, options, &host_touse, port_touse, Intent::Login, start).await;
+    shutoff(&mut stream).await;
+    res
+}
+
+async fn login_exchange_on(stream: &mut TcpStream, options: &PingOptions, host: &str, port: u16, intent: Intent, start: &LoginStart) -> Result<LoginReply, PingError> {
+    let dur = options.timeout_duration();
+    let protocol = options.protocol;
+    let handshake = Handshake { protocol, host: host.to_string(), port, next_state: intent };
+    with_timeout(dur, Phase::Handshake, write_packet(stream, Phase::Handshake, &handshake)).await?;
+    with_timeout(dur, Phase::Handshake, write_versioned_packet(stream, Phase::Handshake, protocol, start)).await?;
+
+    let content = with_timeout(dur, Phase::Login, read_frame(stream, Phase::Login, options.max_response_size)).await?;
+    LoginReply::decode(protocol, &content)
+}
+
+/// Player presented to the server by [`login_probe`]
+#[derive(Clone, Debug)]
+pub struct LoginOptions {
+    pub username: String,
+    /// Sent since 1.19.1, all zeros when required and not set
+    pub uuid: Option<u128>,
+    /// Handshake with the Transfer intent of 1.20.5 instead of Login, as a player sent over by another server
+    pub transfer: bool,
+}
+
+impl LoginOptions {
+    fn login_start(&self) -> LoginStart {
+        LoginStart { name: self.username.clone(), uuid: self.uuid }
+    }
+
+    fn intent(&self) -> Intent {
+        if self.transfer {
+            Intent::Transfer
+        } else {
+            Intent::Login
+        }
+    }
+}
+
+impl Default for LoginOptions {
+    fn default() -> Self {
+        LoginOptions { username: PROBE_USERNAME.to_string(), uuid: None, transfer: false }
+    }
+}
+
+/// What the server answered a login attempt with
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoginProbe {
+    pub protocol: ProtocolNum,
+    /// Intent of the handshake, Login or Transfer
+    pub intent: Intent,
+    /// Threshold of the Set Compression received before `reply`
+    pub compression_threshold: Option<i32>,
+    /// Last packet read. Set Compression itself when the packet following it was compressed.
+    pub reply: LoginReply,
+}
+
+impl LoginProbe {
+    /// True for online mode, false for offline mode, `None` when the server did not go on with the login
+    pub fn online_mode(&self) -> Option<bool> {
+        match self.reply {
+            LoginReply::EncryptionRequest(_) => Some(true),
+            LoginReply::LoginSuccess(_) | LoginReply::SetCompression(_) => Some(false),
+            LoginReply::PluginRequest(_) if self.compression_threshold.is_some() => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Length of the DER encoded public key of the Encryption Request
+    pub fn public_key_len(&self) -> Option<usize> {
+        match &self.reply {
+            LoginReply::EncryptionRequest(v) => Some(v.public_key.len()),
+            _ => None,
+        }
+    }
+
+    pub fn verify_token_len(&self) -> Option<usize> {
+        match &self.reply {
+            LoginReply::EncryptionRequest(v) => Some(v.verify_token.len()),
+            _ => None,
+        }
+    }
+
+    pub fn login_success(&self) -> Option<&LoginSuccess> {
+        match &self.reply {
+            LoginReply::LoginSuccess(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Reason of the Login Disconnect as plain text, such as `You are not whitelisted on this server!`
+    pub fn disconnect_reason(&self) -> Option<String> {
+        self.reply.disconnect_text()
+    }
+
+    /// For probes with the Transfer intent, whether the server let the transferred player in,
+    /// `accepts-transfers` in server.properties. A disconnect for another reason still means the transfer was accepted.
+    pub fn accepts_transfers(&self) -> Option<bool> {
+        match self.intent {
+            Intent::Transfer => Some(!self.reply.is_transfer_refusal()),
+            _ => None,
+        }
+    }
+}
+
+/// Logs in as the given player with `options.protocol` and reports how far the server lets the login go.
+/// A Set Compression is followed by the next packet, Login Success on offline mode servers.
+pub async fn login_probe(host: &str, port: u16, options: &PingOptions, login: &LoginOptions) -> Result<LoginProbe, PingError> {
+    options.check_protocol()?;
+    let host_touse = handshake_host(host, options);
+    let port_touse = port_or_default(port);
+    let mut stream = open_stream(host, port_touse, options).await?;
+    let res = login_probe_on(&mut stream, options, &host_touse, port_touse, login).await;
+    shutoff(&mut stream).await;
+    res
+}
+
+async fn login_probe_on(stream: &mut TcpStream, options: &PingOptions, host: &str, port: u16, login: &LoginOptions) -> Result<LoginProbe, PingError> {
+    let dur = options.timeout_duration();
+    let protocol = options.protocol;
+    let intent = login.intent();
+    let reply = login_exchange_on(stream, options, host, port, intent, &login.login_start()).await?;
+    let LoginReply::SetCompression(threshold) = reply else {
+        return Ok(LoginProbe { protocol, intent, compression_threshold: None, reply });
+    };
+
+    let frame = with_timeout(dur, Phase::Login, read_frame(stream, Phase::Login, options.max_response_size)).await?;
+    let mut content = frame.as_slice();
+    if read_varint(&mut content)? != 0 {
+        // Compressed with zlib, the probe stops at the threshold
+        return Ok(LoginProbe { protocol, intent, compression_threshold: Some(threshold), reply });
+    }
+    let reply = LoginReply::decode(protocol, content)?;
+    Ok(LoginProbe { protocol, intent, compression_threshold: Some(threshold), reply })
+}
+
+/// Whether the server lets players transferred from another server log in.
+/// Servers before 1.20.5 do not know the Transfer intent and close the connection, which counts as refusing.
+pub async fn accepts_transfers(host: &str, port: u16, options: &PingOptions) -> Result<bool, PingError> {
+    let login = LoginOptions { transfer: true, ..Default::default() };
+    match login_probe(host, port, options, &login).await {
+        Ok(probe) => Ok(probe.accepts_transfers() == Some(true)),
+        Err(PingError::Truncated(Phase::Login)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Outcome of logging in with one protocol number
+#[derive(Debug)]
+pub struct SupportEntry {
+    pub protocol: ProtocolNum,
+    pub reply: Result<LoginReply, PingError>,
+}
+
+impl SupportEntry {
+    /// Release names of the protocol number, `1.8–1.8.9`
+    pub fn versions(&self) -> Option<String> {
+        VersionRegistry::current().display_name(self.protocol)
+    }
+
+    pub fn is_accepted(&self) -> bool {
+        self.reply.as_ref().is_ok_and(|v| v.is_accepted())
+    }
+}
+
+/// Logs in with every release protocol number of the current registry, oldest first.
+/// `interval` is waited between attempts, for servers throttling connections.
+/// Fails as soon as the server cannot be reached.
+pub async fn support_matrix(host: &str, port: u16, options: &PingOptions, interval: Duration) -> Result<Vec<SupportEntry>, PingError> {
+    let start = LoginOptions::default().login_start();
+    let mut res = Vec::new();
+    for (i, protocol) in VersionRegistry::current().protocols().into_iter().enumerate() {
+        if i > 0 && !interval.is_zero() {
+            tokio::time::sleep(interval).await;
+        }
+        let options = PingOptions { protocol, ..options.clone() };
+        let reply = login_exchange(host, port, &options, &start).await;
+        if let Err(e) = &reply {
+            if e.is_unreachable() {
+                return Err(reply.unwrap_err());
+            }
+        }
+        res.push(SupportEntry { protocol, reply });
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use crate::protocol::*;
+    use crate::testing::{MockLogin, MockLoginScript, MockScript, MockServer, StubResolver};
+
+    use super::*;
+
+    #[test]
+    fn test_is_version_mismatch() {
+        assert!(LoginReply::Disconnect(r#"{"text":"Outdated client! Please use 1.21.8"}"#.to_string()).is_version_mismatch());
+        assert!(LoginReply::Disconnect(r#""Outdated server! I'm still on 1.8.9""#.to_string()).is_version_mismatch());
+        assert!(LoginReply::Disconnect(r#"{"translate":"multiplayer.disconnect.incompatible","with":["1.21.8"]}"#.to_string()).is_version_mismatch());
+        assert!(!LoginReply::Disconnect(r#"{"text":"You are banned"}"#.to_string()).is_version_mismatch());
+        assert!(!LoginReply::SetCompression(256).is_version_mismatch());
+        assert_eq!(Some("Whitelisted".to_string()), LoginReply::Disconnect("Whitelisted".to_string()).disconnect_text());
+    }
+
+    #[tokio::test]
+    async fn test_support_matrix() {
+        let server = MockServer::start(MockLoginScript {
+            protocols: Some(MINECRAFT_1_20_5..=MINECRAFT_1_21_4),
+            ..Default::default()
+        }).await;
+        let options = PingOptions { timeout: 1, ..Default::default() };
+        let matrix = support_matrix("127.0.0.1", server.port(), &options, Duration::ZERO).await.unwrap();
+        assert_eq!(VersionRegistry::builtin().protocols().len(), matrix.len());
+        for entry in matrix.iter() {
+            let accepted = (MINECRAFT_1_20_5..=MINECRAFT_1_21_4).contains(&entry.protocol);
+            assert_eq!(accepted, entry.is_accepted(), "protocol {}", entry.protocol);
+            match entry.reply.as_ref().unwrap() {
+                LoginReply::EncryptionRequest(v) => assert_eq!(4, v.verify_token.len()),
+                v => assert!(v.is_version_mismatch()),
+            }
+        }
+        assert_eq!(Some("1.8\u{2013}1.8.9".to_string()), matrix[3].versions());
+
+        let logins = server.logins();
+        assert_eq!(matrix.len(), logins.len());
+        assert!(logins.iter().all(|v| v.name == PROBE_USERNAME));
+
+        let server = MockServer::start(MockLoginScript { reply: MockLogin::Compress(256), ..Default::default() }).await;
+        let options = PingOptions { protocol: MINECRAFT_1_8, timeout: 1, ..Default::default() };
+        let start = LoginStart { name: PROBE_USERNAME.to_string(), uuid: None };
+        assert_eq!(LoginReply::SetCompression(256), login_exchange("127.0.0.1", server.port(), &options, &start).await.unwrap());
+        let options = PingOptions { protocol: MINECRAFT_1_8 + 1, ..options };
+        assert!(matches!(login_exchange("127.0.0.1", server.port(), &options, &start).await, Err(PingError::UnknownProtocol(_))));
+    }
+
+    #[tokio::test]
+    async fn test_login_probe() {
+        let options = PingOptions { protocol: LATEST, timeout: 1, ..Default::default() };
+        let login = LoginOptions { username: "Notch".to_string(), uuid: Some(0x069a79f444e94726a5befca90e38aaf5), transfer: false };
+
+        let server = MockServer::start(MockScript::default()).await;
+        let probe = login_probe("127.0.0.1", server.port(), &options, &login).await.unwrap();
+        assert_eq!(Some(true), probe.online_mode());
+        assert_eq!(Some(162), probe.public_key_len());
+        assert_eq!(Some(4), probe.verify_token_len());
+        assert_eq!(None, probe.compression_threshold);
+        assert_eq!(vec![login.login_start()], server.logins());
+
+        let server = MockServer::start(MockLoginScript { reply: MockLogin::Success, ..Default::default() }).await;
+        let probe = login_probe("127.0.0.1", server.port(), &options, &login).await.unwrap();
+        assert_eq!(Some(false), probe.online_mode());
+        assert_eq!(Some(0x069a79f444e94726a5befca90e38aaf5), probe.login_success().map(|v| v.uuid));
+
+        let server = MockServer::start(MockLoginScript { reply: MockLogin::Compress(256), ..Default::default() }).await;
+        let probe = login_probe("127.0.0.1", server.port(), &options, &login).await.unwrap();
+        assert_eq!(Some(false), probe.online_mode());
+        assert_eq!(Some(256), probe.compression_threshold);
+        assert_eq!(Some("Notch"), probe.login_success().map(|v| v.name.as_str()));
+
+        let reason = r#"{"translate":"multiplayer.disconnect.not_whitelisted","fallback":"","text":"You are not whitelisted on this server!"}"#;
+        let server = MockServer::start(MockLoginScript { reply: MockLogin::Disconnect(reason.to_string()), ..Default::default() }).await;
+        let probe = login_probe("127.0.0.1", server.port(), &options, &login).await.unwrap();
+        assert_eq!(None, probe.online_mode());
+        assert_eq!(Some("You are not whitelisted on this server!".to_string()), probe.disconnect_reason());
+
+        let options = PingOptions { protocol: LATEST + 1, ..options };
+        assert!(matches!(login_probe("127.0.0.1", server.port(), &options, &login).await, Err(PingError::UnknownProtocol(_))));
+    }
+
+    #[tokio::test]
+    async fn test_bungee_forwarding() {
+        let server = MockServer::start(MockLoginScript { reply: MockLogin::Success, bungee_forwarding: true, ..Default::default() }).await;
+        let options = PingOptions { protocol: LATEST, timeout: 1, ..Default::default() };
+        let probe = login_probe("127.0.0.1", server.port(), &options, &LoginOptions::default()).await.unwrap();
+        assert!(probe.disconnect_reason().unwrap().contains("IP forwarding"));
+
+        let forwarding = BungeeForwarding { client_ip: "203.0.113.7".parse().unwrap(), uuid: 1, properties: Some(serde_json::json!([])) };
+        let options = PingOptions { forwarding: Some(forwarding.clone()), ..options };
+        let probe = login_probe("127.0.0.1", server.port(), &options, &LoginOptions::default()).await.unwrap();
+        assert_eq!(Some(false), probe.online_mode());
+        let handshakes = server.handshakes();
+        assert_eq!(Some(("127.0.0.1".to_string(), forwarding)), BungeeForwarding::parse(&handshakes[1].host));
+    }
+
+    #[tokio::test]
+    async fn test_accepts_transfers() {
+        let options = PingOptions { protocol: LATEST, timeout: 1, ..Default::default() };
+        let server = MockServer::start(MockScript::default()).await;
+        assert!(!accepts_transfers("127.0.0.1", server.port(), &options).await.unwrap());
+        let server = MockServer::start(MockLoginScript { accepts_transfers: true, ..Default::default() }).await;
+        assert!(accepts_transfers("127.0.0.1", server.port(), &options).await.unwrap());
+        assert_eq!(vec![Intent::Transfer], server.handshakes().iter().map(|v| v.next_state).collect::<Vec<Intent>>());
+
+        let probe = login_probe("127.0.0.1", server.port(), &options, &LoginOptions::default()).await.unwrap();
+        assert_eq!(None, probe.accepts_transfers());
+    }
+
+    #[tokio::test]
+    async fn test_support_matrix_unreachable() {
+        let options = PingOptions { timeout: 1, ..Default::default() };
+        let _stub = StubResolver::new().unresolvable("doesntexist.local").install();
+        let res = support_matrix("doesntexist.local", DEFAULT_PORT, &options, Duration::ZERO).await;
+        assert!(matches!(res, Err(PingError::Resolve(_))));
+
+        // Nothing listens on the port once the listener is dropped
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let res = support_matrix("127.0.0.1", closed, &options, Duration::ZERO).await;
+        assert!(matches!(res, Err(PingError::ConnectionRefused(_))));
+    }
+}
@@ -1,33 +1,594 @@
-use std::io::{stdout, Write};
+use std::fmt::Display;
+use std::fs;
+use std::io::{stdin, stdout, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::Duration;
 
-use clap::{ArgAction, command, Parser};
+use clap::{ArgAction, Args, Parser, Subcommand};
+use serde_json::Value;
 
-use crate::protocol::{DEFAULT_PORT, MINECRAFT_1_8, ping, ProtocolNum};
+use crate::bedrock::{bedrock_ping, BEDROCK_DEFAULT_PORT};
+use crate::dns::{IpMode, split_host_port};
+use crate::forge::ClientFlavor;
+use crate::haproxy::{ProxyHeader, ProxyTlv, ProxyVersion};
+use crate::legacy::LegacyVariant;
+use crate::login::{login_probe, LoginOptions, LoginReply, PROBE_USERNAME, support_matrix};
+use crate::packet::{BungeeForwarding, format_uuid, parse_uuid};
+use crate::protocol::{DEFAULT_MAX_RESPONSE_SIZE, DEFAULT_PORT, LATEST, MINECRAFT_1_8, ping_with_options, PingOptions, ProtocolNum};
+use crate::proxy::Proxy;
+use crate::query::{query_basic, query_full};
+use crate::rcon::{RconClient, RconOptions, RCON_DEFAULT_PORT};
+use crate::server::{favicon_from_png, ServerOptions, StatusServer};
+use crate::status::{StatusPlayers, StatusResponse, StatusVersion};
+use crate::version::{parse_protocol, VersionRegistry};
 
 mod protocol;
 mod packet;
+mod bedrock;
 mod dns;
+mod error;
+mod forge;
+mod haproxy;
+mod legacy;
+mod login;
+mod proxy;
+mod query;
+mod rcon;
+mod server;
+mod status;
+mod version;
+#[cfg(any(test, feature = "test-util"))]
+mod testing;
 
 #[derive(Parser)]
-#[command(about = "Minecraft Server List Ping tool", long_about = None, disable_help_flag = true)]
+#[command(about = "Minecraft Server List Ping tool", long_about = None, disable_help_flag = true,
+    disable_help_subcommand = true, subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 struct RollingGlassArguments {
     #[arg(long = "help", action = ArgAction::Help, help = "Print this help information")]
     _help: Option<bool>,
-    #[arg(short, long, help = "Server host")]
+    #[command(subcommand)]
+    mode: Option<Mode>,
+    #[arg(short, long, required = true, help = "Server host, optionally with port as host:port or [IPv6]:port")]
+    host: Option<String>,
+    #[arg(long)]
+    fakehost: Option<String>,
+    #[arg(short, long, default_value_t = DEFAULT_PORT, help = "Server port")]
+    port: u16,
+    #[arg(long, default_value_t = MINECRAFT_1_8.to_string(), help = "Protocol number or version, e.g. 47, 0x40000001 or 1.20.4, or auto to ping again with the server's protocol")]
+    protocol: String,
+    #[arg(long, value_name = "FILE", help = "minecraft-data protocolVersions.json merged with the built-in versions")]
+    versions: Option<PathBuf>,
+    #[arg(long, help = "Send the protocol number even if it is not a known release, snapshot or pre-release")]
+    any_protocol: bool,
+    #[arg(long, default_value_t = 5u8, help = "Connection timeout in seconds")]
+    timeout: u8,
+    #[command(flatten)]
+    connection: ConnectionArguments,
+    #[arg(long, num_args = 0..=1, default_missing_value = "1.6", value_name = "VARIANT", help = "Use the legacy ping (beta1.8, 1.4 or 1.6)")]
+    legacy: Option<LegacyVariant>,
+    #[arg(long, conflicts_with = "legacy", help = "Fall back to the legacy ping if the server does not answer the handshake")]
+    legacy_fallback: bool,
+    #[arg(long, default_value_t = DEFAULT_MAX_RESPONSE_SIZE, help = "Largest status response accepted, in bytes")]
+    max_response_size: usize,
+    #[arg(long, default_value = "vanilla", value_name = "FLAVOR", help = "Client announced in the handshake host (vanilla or forge)")]
+    client: ClientFlavor,
+    #[arg(long, help = "Print the Forge mod list instead of the status JSON")]
+    mods: bool,
+}
+
+#[derive(Subcommand)]
+enum Mode {
+    #[command(about = "Ping a Bedrock Edition server over RakNet", disable_help_flag = true)]
+    Bedrock(BedrockArguments),
+    #[command(about = "Log in to tell online mode, compression and disconnect reasons", disable_help_flag = true)]
+    Login(Box<LoginArguments>),
+    #[command(about = "Log in with every known protocol to list the client versions a server accepts", disable_help_flag = true)]
+    Matrix(Box<MatrixArguments>),
+    #[command(about = "Query a Java Edition server with enable-query=true", disable_help_flag = true)]
+    Query(QueryArguments),
+    #[command(about = "Run commands over RCON, interactively unless --command is given", disable_help_flag = true)]
+    Rcon(RconArguments),
+    #[command(about = "Answer server list pings with a fixed status", disable_help_flag = true)]
+    Serve(ServeArguments),
+}
+
+#[derive(Args)]
+struct AddressFamilyArguments {
+    #[arg(short = '4', long, conflicts_with = "ipv6", help = "Only use IPv4 addresses")]
+    ipv4: bool,
+    #[arg(short = '6', long, help = "Only use IPv6 addresses")]
+    ipv6: bool,
+}
+
+/// How the Java Edition subcommands reach the server, shared by ping, login and matrix
+#[derive(Args)]
+struct ConnectionArguments {
+    #[command(flatten)]
+    family: AddressFamilyArguments,
+    #[command(flatten)]
+    forwarding: ForwardingArguments,
+    #[arg(long, value_name = "URL", help = "Connect through a proxy, socks5://[user:password@]host:port, socks5h:// to resolve the host on the proxy or http://")]
+    proxy: Option<Proxy>,
+    #[command(flatten)]
+    proxy_protocol: ProxyProtocolArguments,
+}
+
+#[derive(Args)]
+struct ForwardingArguments {
+    #[arg(long, value_name = "IP", help = "Forward this player address in the handshake like BungeeCord with ip_forward")]
+    forward_ip: Option<IpAddr>,
+    #[arg(long, value_name = "UUID", requires = "forward_ip", value_parser = parse_uuid_arg, help = "Player UUID forwarded with --forward-ip [default: all zeros]")]
+    forward_uuid: Option<u128>,
+    #[arg(long, value_name = "JSON", requires = "forward_ip", value_parser = parse_json_arg, help = "Profile properties forwarded with --forward-ip, as a JSON array")]
+    forward_properties: Option<Value>,
+}
+
+#[derive(Args)]
+struct ProxyProtocolArguments {
+    #[arg(long, num_args = 0..=1, default_missing_value = "v2", value_name = "VERSION", help = "Send a PROXY protocol header (v1 or v2) before the handshake")]
+    proxy_protocol: Option<ProxyVersion>,
+    #[arg(long, value_name = "ADDR", requires = "proxy_protocol", help = "Client address in the PROXY header [default: local address]")]
+    proxy_source: Option<SocketAddr>,
+    #[arg(long, value_name = "ADDR", requires = "proxy_protocol", help = "Server address in the PROXY header, required when the proxy resolves the host [default: server connected to]")]
+    proxy_destination: Option<SocketAddr>,
+    #[arg(long, value_name = "TYPE=VALUE", requires = "proxy_protocol", value_parser = parse_tlv_arg, help = "TLV of a v2 header, e.g. 0x02=mc.example.com, can be repeated")]
+    proxy_tlv: Vec<ProxyTlv>,
+}
+
+impl ConnectionArguments {
+    fn ping_options(self) -> PingOptions {
+        PingOptions {
+            ip_mode: self.family.ip_mode(),
+            forwarding: self.forwarding.forwarding(),
+            proxy_protocol: self.proxy_protocol.header(),
+            proxy: self.proxy,
+            ..Default::default()
+        }
+    }
+}
+
+impl ProxyProtocolArguments {
+    fn header(self) -> Option<ProxyHeader> {
+        Some(ProxyHeader { version: self.proxy_protocol?, source: self.proxy_source, destination: self.proxy_destination, tlvs: self.proxy_tlv })
+    }
+}
+
+impl ForwardingArguments {
+    fn forwarding(self) -> Option<BungeeForwarding> {
+        Some(BungeeForwarding { client_ip: self.forward_ip?, uuid: self.forward_uuid.unwrap_or_default(), properties: self.forward_properties })
+    }
+}
+
+impl AddressFamilyArguments {
+    fn ip_mode(&self) -> IpMode {
+        if self.ipv4 {
+            IpMode::V4Only
+        } else if self.ipv6 {
+            IpMode::V6Only
+        } else {
+            IpMode::Dual
+        }
+    }
+}
+
+#[derive(Args)]
+struct BedrockArguments {
+    #[arg(long = "help", action = ArgAction::Help, help = "Print this help information")]
+    _help: Option<bool>,
+    #[arg(short, long, help = "Server host, optionally with port as host:port or [IPv6]:port")]
+    host: String,
+    #[arg(short, long, default_value_t = BEDROCK_DEFAULT_PORT, help = "Server port")]
+    port: u16,
+    #[arg(long, default_value_t = 5u8, help = "Timeout in seconds")]
+    timeout: u8,
+    #[command(flatten)]
+    family: AddressFamilyArguments,
+}
+
+#[derive(Args)]
+struct LoginArguments {
+    #[arg(long = "help", action = ArgAction::Help, help = "Print this help information")]
+    _help: Option<bool>,
+    #[arg(short, long, help = "Server host, optionally with port as host:port or [IPv6]:port")]
     host: String,
     #[arg(long)]
     fakehost: Option<String>,
     #[arg(short, long, default_value_t = DEFAULT_PORT, help = "Server port")]
     port: u16,
-    #[arg(long, default_value_t = MINECRAFT_1_8, help = "Protocol number")]
+    #[arg(long, default_value_t = LATEST, value_parser = parse_protocol, help = "Protocol number or version, e.g. 47 or 1.20.4")]
     protocol: ProtocolNum,
-    #[arg(long, default_value_t = 5u8, help = "Connection timeout in seconds")]
-    timeout: u8
+    #[arg(long, default_value = PROBE_USERNAME, help = "Player name sent in Login Start")]
+    username: String,
+    #[arg(long, value_parser = parse_uuid_arg, help = "Player UUID sent in Login Start since 1.19.1, with or without hyphens")]
+    uuid: Option<u128>,
+    #[arg(long, help = "Log in as a player transferred from another server, to tell whether the server accepts transfers")]
+    transfer: bool,
+    #[arg(long, default_value_t = 5u8, help = "Timeout in seconds")]
+    timeout: u8,
+    #[command(flatten)]
+    connection: ConnectionArguments,
+}
+
+#[derive(Args)]
+struct MatrixArguments {
+    #[arg(long = "help", action = ArgAction::Help, help = "Print this help information")]
+    _help: Option<bool>,
+    #[arg(short, long, help = "Server host, optionally with port as host:port or [IPv6]:port")]
+    host: String,
+    #[arg(long)]
+    fakehost: Option<String>,
+    #[arg(short, long, default_value_t = DEFAULT_PORT, help = "Server port")]
+    port: u16,
+    #[arg(long, default_value_t = 5u8, help = "Timeout in seconds")]
+    timeout: u8,
+    #[arg(long, default_value_t = 0, value_name = "MS", help = "Milliseconds to wait between logins, for servers throttling connections")]
+    interval: u64,
+    #[arg(long, value_name = "FILE", help = "minecraft-data protocolVersions.json merged with the built-in versions")]
+    versions: Option<PathBuf>,
+    #[command(flatten)]
+    connection: ConnectionArguments,
+}
+
+#[derive(Args)]
+struct QueryArguments {
+    #[arg(long = "help", action = ArgAction::Help, help = "Print this help information")]
+    _help: Option<bool>,
+    #[arg(short, long, help = "Server host, optionally with port as host:port or [IPv6]:port")]
+    host: String,
+    #[arg(short, long, default_value_t = DEFAULT_PORT, help = "Query port")]
+    port: u16,
+    #[arg(long, default_value_t = 5u8, help = "Timeout in seconds")]
+    timeout: u8,
+    #[arg(long, help = "Request the full stat with plugins and player list")]
+    full: bool,
+    #[command(flatten)]
+    family: AddressFamilyArguments,
+}
+
+#[derive(Args)]
+struct RconArguments {
+    #[arg(long = "help", action = ArgAction::Help, help = "Print this help information")]
+    _help: Option<bool>,
+    #[arg(short, long, help = "Server host, optionally with port as host:port or [IPv6]:port")]
+    host: String,
+    #[arg(short, long, default_value_t = RCON_DEFAULT_PORT, help = "RCON port")]
+    port: u16,
+    #[arg(long, help = "RCON password")]
+    password: String,
+    #[arg(short, long, help = "Run a single command and exit")]
+    command: Option<String>,
+    #[arg(long, default_value_t = 5u8, help = "Timeout in seconds")]
+    timeout: u8,
+    #[command(flatten)]
+    family: AddressFamilyArguments,
+}
+
+#[derive(Args)]
+struct ServeArguments {
+    #[arg(long = "help", action = ArgAction::Help, help = "Print this help information")]
+    _help: Option<bool>,
+    #[arg(short, long, default_value = "0.0.0.0:25565", help = "Address to listen on")]
+    bind: SocketAddr,
+    #[arg(long, default_value = "A Minecraft Server", help = "Description shown in the server list, also the disconnect reason on login")]
+    motd: String,
+    #[arg(long, default_value = "RollingGlass", help = "Version name")]
+    version_name: String,
+    #[arg(long, value_parser = parse_protocol, help = "Protocol number or version, the client's own when omitted")]
+    protocol: Option<ProtocolNum>,
+    #[arg(long, default_value_t = 0, help = "Online player count")]
+    online: i64,
+    #[arg(long, default_value_t = 20, help = "Max player count")]
+    max: i64,
+    #[arg(long, help = "64x64 PNG shown as the server icon")]
+    favicon: Option<PathBuf>,
 }
 
 #[tokio::main]
 async fn main() {
     let args: RollingGlassArguments = RollingGlassArguments::parse();
-    let res = ping(&args.host, args.port, &args.fakehost.unwrap_or_default(), args.protocol, args.timeout).await.expect("Failed to ping");
-    let _ = stdout().write_all(&res);
+    match args.mode {
+        Some(Mode::Bedrock(args)) => bedrock(args).await,
+        Some(Mode::Login(args)) => login(*args).await,
+        Some(Mode::Matrix(args)) => matrix(*args).await,
+        Some(Mode::Query(args)) => query(args).await,
+        Some(Mode::Rcon(args)) => rcon(args).await,
+        Some(Mode::Serve(args)) => serve(args).await,
+        None => java(args).await,
+    }
+}
+
+async fn java(args: RollingGlassArguments) {
+    let (host, port) = match split_host_port(&args.host.unwrap_or_default()) {
+        Ok(v) => v,
+        Err(e) => fail("Failed to ping", e),
+    };
+    install_versions(args.versions);
+    let auto_protocol = args.protocol == "auto";
+    let protocol = match parse_protocol(&args.protocol) {
+        Ok(v) => v,
+        Err(_) if auto_protocol => MINECRAFT_1_8,
+        Err(e) => {
+            eprintln!("{e}");
+            exit(1);
+        }
+    };
+    let options = PingOptions {
+        fakehost: args.fakehost.unwrap_or_default(),
+        protocol,
+        timeout: args.timeout,
+        legacy: args.legacy,
+        legacy_fallback: args.legacy_fallback,
+        max_response_size: args.max_response_size,
+        allow_unknown_protocol: args.any_protocol,
+        auto_protocol,
+        client_flavor: args.client,
+        ..args.connection.ping_options()
+    };
+    match ping_with_options(&host, port.unwrap_or(args.port), &options).await {
+        Ok(res) if args.mods => print_mods(&res.response),
+        Ok(res) if res.legacy.is_some() => {
+            let _ = stdout().write_all(&serde_json::to_vec(&res.response).unwrap_or_default());
+        }
+        Ok(res) => {
+            if auto_protocol {
+                let advertised = res.advertised_protocol.map_or("none".to_string(), |v| v.to_string());
+                eprintln!("Pinged with protocol {}, server advertises {advertised}", res.requested_protocol.unwrap_or(protocol));
+            }
+            let _ = stdout().write_all(&res.raw);
+        }
+        Err(e) => fail("Failed to ping", e),
+    }
+}
+
+fn print_mods(response: &StatusResponse) {
+    let info = match response.forge_info() {
+        Ok(Some(v)) => v,
+        Ok(None) => {
+            eprintln!("No Forge mod list in the status");
+            exit(1);
+        }
+        Err(e) => fail("Failed to read the mod list", e),
+    };
+    for forge_mod in &info.mods {
+        println!("{:<32}{}", forge_mod.id, forge_mod.version.as_deref().unwrap_or("server only"));
+    }
+    if info.truncated {
+        eprintln!("Mod list truncated by the server");
+    }
+}
+
+async fn bedrock(args: BedrockArguments) {
+    let (host, port) = match split_host_port(&args.host) {
+        Ok(v) => v,
+        Err(e) => fail("Failed to ping", e),
+    };
+    let options = PingOptions {
+        timeout: args.timeout,
+        ip_mode: args.family.ip_mode(),
+        ..Default::default()
+    };
+    match bedrock_ping(&host, port.unwrap_or(args.port), &options).await {
+        Ok(res) => {
+            let _ = stdout().write_all(&serde_json::to_vec(&res.status).unwrap_or_default());
+        }
+        Err(e) => fail("Failed to ping", e),
+    }
+}
+
+async fn login(args: LoginArguments) {
+    let (host, port) = match split_host_port(&args.host) {
+        Ok(v) => v,
+        Err(e) => fail("Failed to log in", e),
+    };
+    let options = PingOptions {
+        fakehost: args.fakehost.unwrap_or_default(),
+        protocol: args.protocol,
+        timeout: args.timeout,
+        ..args.connection.ping_options()
+    };
+    let login = LoginOptions { username: args.username, uuid: args.uuid, transfer: args.transfer };
+    let probe = match login_probe(&host, port.unwrap_or(args.port), &options, &login).await {
+        Ok(v) => v,
+        Err(e) => fail("Failed to log in", e),
+    };
+    match probe.accepts_transfers() {
+        Some(true) => println!("Transfers accepted"),
+        Some(false) => println!("Transfers refused"),
+        None => {}
+    }
+    if let Some(threshold) = probe.compression_threshold {
+        println!("Compression threshold {threshold}");
+    }
+    match &probe.reply {
+        LoginReply::EncryptionRequest(v) => {
+            println!("Online mode, public key of {} bytes, verify token of {} bytes", v.public_key.len(), v.verify_token.len());
+        }
+        LoginReply::LoginSuccess(v) => println!("Offline mode, logged in as {} {}", v.name, format_uuid(v.uuid)),
+        LoginReply::SetCompression(_) => println!("Offline mode"),
+        LoginReply::PluginRequest(channel) => println!("Plugin request on {channel}"),
+        LoginReply::Disconnect(_) => println!("Disconnected: {}", probe.disconnect_reason().unwrap_or_default()),
+    }
+}
+
+async fn matrix(args: MatrixArguments) {
+    let (host, port) = match split_host_port(&args.host) {
+        Ok(v) => v,
+        Err(e) => fail("Failed to probe versions", e),
+    };
+    install_versions(args.versions);
+    let options = PingOptions {
+        fakehost: args.fakehost.unwrap_or_default(),
+        timeout: args.timeout,
+        ..args.connection.ping_options()
+    };
+    let interval = Duration::from_millis(args.interval);
+    let entries = match support_matrix(&host, port.unwrap_or(args.port), &options, interval).await {
+        Ok(v) => v,
+        Err(e) => fail("Failed to probe versions", e),
+    };
+    for entry in entries {
+        let outcome = match &entry.reply {
+            Ok(LoginReply::EncryptionRequest(_)) => "accepted, encryption request".to_string(),
+            Ok(LoginReply::LoginSuccess(_)) => "accepted, login success".to_string(),
+            Ok(LoginReply::SetCompression(threshold)) => format!("accepted, compression threshold {threshold}"),
+            Ok(LoginReply::PluginRequest(channel)) => format!("accepted, plugin request on {channel}"),
+            Ok(reply) if reply.is_version_mismatch() => format!("outdated: {}", reply.disconnect_text().unwrap_or_default()),
+            Ok(reply) => format!("rejected: {}", reply.disconnect_text().unwrap_or_default()),
+            Err(e) => format!("failed: {e}"),
+        };
+        println!("{:>10}  {:<24}{outcome}", entry.protocol, entry.versions().unwrap_or_default());
+    }
+}
+
+async fn query(args: QueryArguments) {
+    let (host, port) = match split_host_port(&args.host) {
+        Ok(v) => v,
+        Err(e) => fail("Failed to query", e),
+    };
+    let options = PingOptions {
+        timeout: args.timeout,
+        ip_mode: args.family.ip_mode(),
+        ..Default::default()
+    };
+    let port = port.unwrap_or(args.port);
+    let res = if args.full {
+        query_full(&host, port, &options).await.map(|v| serde_json::to_vec(&v))
+    } else {
+        query_basic(&host, port, &options).await.map(|v| serde_json::to_vec(&v))
+    };
+    match res {
+        Ok(res) => {
+            let _ = stdout().write_all(&res.unwrap_or_default());
+        }
+        Err(e) => fail("Failed to query", e),
+    }
+}
+
+async fn rcon(args: RconArguments) {
+    let (host, port) = match split_host_port(&args.host) {
+        Ok(v) => v,
+        Err(e) => fail("RCON failed", e),
+    };
+    let port = port.unwrap_or(args.port);
+    let options = RconOptions { timeout: args.timeout, ip_mode: args.family.ip_mode() };
+    let mut client = match RconClient::connect(&host, port, &args.password, &options).await {
+        Ok(v) => v,
+        Err(e) => fail("RCON failed", e),
+    };
+    if let Some(command) = args.command {
+        match client.command(&command).await {
+            Ok(res) => println!("{res}"),
+            Err(e) => fail("RCON failed", e),
+        }
+        client.close().await;
+        return;
+    }
+
+    let mut line = String::new();
+    loop {
+        print!("> ");
+        let _ = stdout().flush();
+        line.clear();
+        if stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let command = line.trim();
+        if command.is_empty() {
+            continue;
+        }
+        if command == "exit" || command == "quit" {
+            break;
+        }
+        match client.command(command).await {
+            Ok(res) => println!("{res}"),
+            Err(e) => {
+                eprintln!("{e}");
+                // Replies to the failed command may still arrive, a new connection keeps them apart
+                client.close().await;
+                client = match RconClient::connect(&host, port, &args.password, &options).await {
+                    Ok(v) => v,
+                    Err(e) => fail("RCON failed", e),
+                };
+            }
+        }
+    }
+    client.close().await;
+}
+
+async fn serve(args: ServeArguments) {
+    let favicon = match args.favicon.map(fs::read).transpose() {
+        Ok(v) => v.map(|png| favicon_from_png(&png)),
+        Err(e) => {
+            eprintln!("Failed to read favicon: {e}");
+            exit(1);
+        }
+    };
+    let options = ServerOptions {
+        status: StatusResponse {
+            version: Some(StatusVersion { name: args.version_name, protocol: args.protocol.unwrap_or_default() }),
+            players: Some(StatusPlayers { max: args.max, online: args.online, sample: Vec::new() }),
+            description: Some(serde_json::Value::String(args.motd)),
+            favicon,
+            ..Default::default()
+        },
+        echo_protocol: args.protocol.is_none(),
+        ..Default::default()
+    };
+    let server = match StatusServer::bind(args.bind, options).await {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to listen on {}: {e}", args.bind);
+            exit(1);
+        }
+    };
+    eprintln!("Listening on {}", server.local_addr().unwrap_or(args.bind));
+    if let Err(e) = server.serve().await {
+        eprintln!("Failed to accept: {e}");
+        exit(1);
+    }
+}
+
+fn parse_uuid_arg(s: &str) -> Result<u128, String> {
+    parse_uuid(s).map_err(|e| e.to_string())
+}
+
+/// `TYPE=VALUE` with the type in decimal or 0x hex and the value as text
+fn parse_tlv_arg(s: &str) -> Result<ProxyTlv, String> {
+    let (kind, value) = s.split_once('=').ok_or_else(|| format!("Expected TYPE=VALUE, got \"{s}\""))?;
+    let kind = match kind.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => kind.parse(),
+    };
+    let kind = kind.map_err(|_| format!("Invalid TLV type in \"{s}\""))?;
+    Ok(ProxyTlv { kind, value: value.as_bytes().to_vec() })
+}
+
+fn parse_json_arg(s: &str) -> Result<Value, String> {
+    serde_json::from_str(s).map_err(|e| e.to_string())
+}
+
+/// Installs the registry merged with the versions file, if any, before protocols are parsed
+fn install_versions(path: Option<PathBuf>) {
+    let Some(path) = path else {
+        return;
+    };
+    match VersionRegistry::from_minecraft_data_file(&path) {
+        Ok(registry) => {
+            if registry.install().is_err() {
+                eprintln!("Failed to load versions from {}: a version registry is already installed", path.display());
+                exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to load versions from {}: {e}", path.display());
+            exit(1);
+        }
+    }
+}
+
+fn fail(context: &str, e: impl Display) -> ! {
+    eprintln!("{context}: {e}");
+    exit(1);
 }
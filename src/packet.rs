@@ -1,44 +1,681 @@
 #![allow(unused)]
 
+use std::net::IpAddr;
+
 use flashlight::create_varint;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::error::{Phase, PingError};
+use crate::protocol::{
+    MINECRAFT_1_16, MINECRAFT_1_19, MINECRAFT_1_19_1, MINECRAFT_1_19_3, MINECRAFT_1_20, MINECRAFT_1_20_5,
+    MINECRAFT_1_21, MINECRAFT_1_8, ProtocolNum,
+};
+
+/// A packet of the Java Edition protocol, encoded without its length prefix and ID
+pub trait Packet: Sized {
+    const ID: i32;
+
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Reads the packet fields from `buf`, advancing it past them
+    fn decode(buf: &mut &[u8]) -> Result<Self, PingError>;
+}
+
+/// A packet whose layout depends on the protocol number of the connection
+pub trait VersionedPacket: Sized {
+    const ID: i32;
+
+    fn encode_for(&self, protocol: ProtocolNum, buf: &mut Vec<u8>);
 
-use crate::protocol::ProtocolNum;
+    /// Reads the packet fields from `buf`, advancing it past them
+    fn decode_for(protocol: ProtocolNum, buf: &mut &[u8]) -> Result<Self, PingError>;
+}
+
+/// Block position as packed since 1.14, x and z in 26 bits, y in 12 bits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+fn take<'a>(buf: &mut &'a [u8], n: usize) -> Result<&'a [u8], PingError> {
+    if buf.len() < n {
+        return Err(PingError::InvalidLength(n as i64));
+    }
+    let (res, rest) = buf.split_at(n);
+    *buf = rest;
+    Ok(res)
+}
+
+pub fn write_varint(buf: &mut Vec<u8>, value: i32) {
+    buf.append(&mut create_varint(value));
+}
+
+pub fn read_varint(buf: &mut &[u8]) -> Result<i32, PingError> {
+    let mut res = 0i32;
+    for i in 0..5 {
+        let byte = *buf.first().ok_or(PingError::MalformedVarInt)?;
+        *buf = &buf[1..];
+        res |= ((byte as i32) & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(res);
+        }
+    }
+    Err(PingError::MalformedVarInt)
+}
+
+pub fn write_varlong(buf: &mut Vec<u8>, value: i64) {
+    let mut value = value as u64;
+    loop {
+        if value & !0x7F == 0 {
+            buf.push(value as u8);
+            return;
+        }
+        buf.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+}
+
+pub fn read_varlong(buf: &mut &[u8]) -> Result<i64, PingError> {
+    let mut res = 0i64;
+    for i in 0..10 {
+        let byte = *buf.first().ok_or(PingError::MalformedVarInt)?;
+        *buf = &buf[1..];
+        res |= ((byte as i64) & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(res);
+        }
+    }
+    Err(PingError::MalformedVarInt)
+}
+
+/// Writes a string prefixed with its length in bytes
+pub fn write_string(buf: &mut Vec<u8>, s: &str) {
+    write_varint(buf, s.len() as i32);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+pub fn read_string(buf: &mut &[u8]) -> Result<String, PingError> {
+    let len = read_varint(buf)?;
+    if len < 0 {
+        return Err(PingError::InvalidLength(len as i64));
+    }
+    let bytes = take(buf, len as usize)?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| PingError::MalformedString)
+}
+
+/// Writes a byte array prefixed with its length
+pub fn write_byte_array(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(buf, bytes.len() as i32);
+    buf.extend_from_slice(bytes);
+}
+
+pub fn read_byte_array(buf: &mut &[u8]) -> Result<Vec<u8>, PingError> {
+    let len = read_varint(buf)?;
+    if len < 0 {
+        return Err(PingError::InvalidLength(len as i64));
+    }
+    Ok(take(buf, len as usize)?.to_vec())
+}
+
+pub fn write_bool(buf: &mut Vec<u8>, value: bool) {
+    buf.push(value as u8);
+}
+
+pub fn read_bool(buf: &mut &[u8]) -> Result<bool, PingError> {
+    Ok(take(buf, 1)?[0] != 0)
+}
+
+pub fn write_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn read_u16(buf: &mut &[u8]) -> Result<u16, PingError> {
+    Ok(u16::from_be_bytes(take(buf, 2)?.try_into().unwrap()))
+}
+
+pub fn write_i64(buf: &mut Vec<u8>, value: i64) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn read_i64(buf: &mut &[u8]) -> Result<i64, PingError> {
+    Ok(i64::from_be_bytes(take(buf, 8)?.try_into().unwrap()))
+}
+
+/// Writes a UUID as two big endian longs
+pub fn write_uuid(buf: &mut Vec<u8>, uuid: u128) {
+    buf.extend_from_slice(&uuid.to_be_bytes());
+}
+
+pub fn read_uuid(buf: &mut &[u8]) -> Result<u128, PingError> {
+    Ok(u128::from_be_bytes(take(buf, 16)?.try_into().unwrap()))
+}
+
+pub fn write_position(buf: &mut Vec<u8>, position: Position) {
+    let packed = ((position.x as i64 & 0x3FFFFFF) << 38) | ((position.z as i64 & 0x3FFFFFF) << 12) | (position.y as i64 & 0xFFF);
+    write_i64(buf, packed);
+}
+
+pub fn read_position(buf: &mut &[u8]) -> Result<Position, PingError> {
+    let packed = read_i64(buf)?;
+    // Arithmetic shifts sign extend each field
+    Ok(Position {
+        x: (packed >> 38) as i32,
+        y: ((packed << 52) >> 52) as i32,
+        z: ((packed << 26) >> 38) as i32,
+    })
+}
 
 fn packet_raw_content_add_varint_length(packet_content: &mut Vec<u8>) {
     packet_content.splice(0..0 , create_varint(packet_content.len() as i32));
 }
 
-pub fn compose_handshake_packet(host: &String, port: u16, protocol: ProtocolNum) -> Vec<u8> {
-    let host_strlen = host.len();
-    let mut host_strlen_varint = create_varint(host_strlen as i32);
-    let host_strlen_varint_bytelen = host_strlen_varint.len();
+/// Encodes a packet with its ID and length prefix
+pub fn encode_packet<P: Packet>(packet: &P) -> Vec<u8> {
+    let mut content: Vec<u8> = Vec::new();
+    write_varint(&mut content, P::ID);
+    packet.encode(&mut content);
+    packet_raw_content_add_varint_length(&mut content);
+    content
+}
 
-    let mut protocol_as_varint = create_varint(protocol as i32);
-    let protocol_varint_bytelen = protocol_as_varint.len();
+/// Encodes a packet in the layout of `protocol`, with its ID and length prefix
+pub fn encode_versioned_packet<P: VersionedPacket>(protocol: ProtocolNum, packet: &P) -> Vec<u8> {
+    let mut content: Vec<u8> = Vec::new();
+    write_varint(&mut content, P::ID);
+    packet.encode_for(protocol, &mut content);
+    packet_raw_content_add_varint_length(&mut content);
+    content
+}
 
-    let handshake_content_len = 1 /* Packet ID */ + protocol_varint_bytelen + host_strlen_varint_bytelen + host_strlen + 2 + 1 /* Port number. State number. */;
-    let mut handshake_content_len_as_varint = create_varint(handshake_content_len as i32);
-    let mut content: Vec<u8> = Vec::with_capacity(handshake_content_len + handshake_content_len_as_varint.len());
+/// Decodes a packet from a frame without its length prefix, `content` starts at the packet ID
+pub fn decode_packet<P: Packet>(content: &[u8]) -> Result<P, PingError> {
+    decode_with(content, P::ID, P::decode)
+}
 
-    content.append(&mut handshake_content_len_as_varint);
-    content.push(0x00);
-    content.append(&mut protocol_as_varint);
-    content.append(&mut host_strlen_varint);
-    content.append(&mut host.as_bytes().to_vec());
-    content.append(&mut port.to_be_bytes().to_vec());
-    content.push(0x01);
+/// Decodes a packet in the layout of `protocol`, `content` starts at the packet ID
+pub fn decode_versioned_packet<P: VersionedPacket>(protocol: ProtocolNum, content: &[u8]) -> Result<P, PingError> {
+    decode_with(content, P::ID, |buf| P::decode_for(protocol, buf))
+}
 
-    content
+fn decode_with<P>(content: &[u8], expected: i32, decode: impl FnOnce(&mut &[u8]) -> Result<P, PingError>) -> Result<P, PingError> {
+    let mut buf = content;
+    let id = read_varint(&mut buf)?;
+    if id != expected {
+        return Err(PingError::UnexpectedPacketId { expected, actual: id });
+    }
+    let res = decode(&mut buf)?;
+    if !buf.is_empty() {
+        return Err(PingError::InvalidLength(buf.len() as i64));
+    }
+    Ok(res)
+}
+
+/// Reads exactly one frame of at most `max` bytes, returns its content starting at the packet ID
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R, phase: Phase, max: usize) -> Result<Vec<u8>, PingError> {
+    let mut len = 0i32;
+    for i in 0..=5 {
+        if i == 5 {
+            return Err(PingError::MalformedVarInt);
+        }
+        let byte = reader.read_u8().await.map_err(|e| PingError::io(phase, e))?;
+        len |= ((byte as i32) & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    if len <= 0 {
+        return Err(PingError::InvalidLength(len as i64));
+    }
+    if len as usize > max {
+        return Err(PingError::OversizedFrame { size: len as usize, max });
+    }
+    let mut content = vec![0u8; len as usize];
+    reader.read_exact(&mut content).await.map_err(|e| PingError::io(phase, e))?;
+    Ok(content)
+}
+
+pub async fn read_packet<R: AsyncRead + Unpin, P: Packet>(reader: &mut R, phase: Phase, max: usize) -> Result<P, PingError> {
+    decode_packet(&read_frame(reader, phase, max).await?)
+}
+
+pub async fn write_packet<W: AsyncWrite + Unpin, P: Packet>(writer: &mut W, phase: Phase, packet: &P) -> Result<(), PingError> {
+    writer.write_all(&encode_packet(packet)).await.map_err(|e| PingError::io(phase, e))
+}
+
+pub async fn write_versioned_packet<W: AsyncWrite + Unpin, P: VersionedPacket>(writer: &mut W, phase: Phase, protocol: ProtocolNum, packet: &P) -> Result<(), PingError> {
+    writer.write_all(&encode_versioned_packet(protocol, packet)).await.map_err(|e| PingError::io(phase, e))
+}
+
+/// State the handshake switches the connection to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Intent {
+    #[default]
+    Status = 1,
+    Login = 2,
+    /// Login of a player transferred from another server, since 1.20.5
+    Transfer = 3,
+}
+
+impl TryFrom<i32> for Intent {
+    type Error = PingError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Intent::Status),
+            2 => Ok(Intent::Login),
+            3 => Ok(Intent::Transfer),
+            _ => Err(PingError::UnknownIntent(value)),
+        }
+    }
+}
+
+/// Handshake, the first packet of every connection
+#[derive(Clone, Debug, PartialEq)]
+pub struct Handshake {
+    pub protocol: ProtocolNum,
+    pub host: String,
+    pub port: u16,
+    pub next_state: Intent,
+}
+
+impl Packet for Handshake {
+    const ID: i32 = 0x00;
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_varint(buf, self.protocol);
+        write_string(buf, &self.host);
+        write_u16(buf, self.port);
+        write_varint(buf, self.next_state as i32);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, PingError> {
+        Ok(Handshake {
+            protocol: read_varint(buf)?,
+            host: read_string(buf)?,
+            port: read_u16(buf)?,
+            next_state: Intent::try_from(read_varint(buf)?)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatusRequest;
+
+impl Packet for StatusRequest {
+    const ID: i32 = 0x00;
+
+    fn encode(&self, buf: &mut Vec<u8>) {}
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, PingError> {
+        Ok(StatusRequest)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatusResponsePacket {
+    pub json: String,
+}
+
+impl Packet for StatusResponsePacket {
+    const ID: i32 = 0x00;
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_string(buf, &self.json);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, PingError> {
+        Ok(StatusResponsePacket { json: read_string(buf)? })
+    }
+}
+
+/// Ping Request, the Pong Response has the same layout
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PingRequest {
+    pub payload: i64,
+}
+
+impl Packet for PingRequest {
+    const ID: i32 = 0x01;
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_i64(buf, self.payload);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, PingError> {
+        Ok(PingRequest { payload: read_i64(buf)? })
+    }
+}
+
+pub type PongResponse = PingRequest;
+
+/// Disconnect during login, the reason is a JSON chat component
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoginDisconnect {
+    pub reason: String,
+}
+
+impl Packet for LoginDisconnect {
+    const ID: i32 = 0x00;
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_string(buf, &self.reason);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, PingError> {
+        Ok(LoginDisconnect { reason: read_string(buf)? })
+    }
+}
+
+/// Login Start, the player name and, since 1.19.1, their UUID.
+/// Signature data of 1.19 and 1.19.1 is never sent, and skipped when received.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoginStart {
+    pub name: String,
+    pub uuid: Option<u128>,
+}
+
+impl VersionedPacket for LoginStart {
+    const ID: i32 = 0x00;
+
+    fn encode_for(&self, protocol: ProtocolNum, buf: &mut Vec<u8>) {
+        write_string(buf, &self.name);
+        let uuid = self.uuid.unwrap_or_default();
+        match protocol {
+            ..MINECRAFT_1_19 => {}
+            MINECRAFT_1_19 => write_bool(buf, false),
+            MINECRAFT_1_19_1 => {
+                write_bool(buf, false);
+                write_bool(buf, self.uuid.is_some());
+                if let Some(uuid) = self.uuid {
+                    write_uuid(buf, uuid);
+                }
+            }
+            MINECRAFT_1_19_3..=MINECRAFT_1_20 => {
+                write_bool(buf, self.uuid.is_some());
+                if let Some(uuid) = self.uuid {
+                    write_uuid(buf, uuid);
+                }
+            }
+            _ => write_uuid(buf, uuid),
+        }
+    }
+
+    fn decode_for(protocol: ProtocolNum, buf: &mut &[u8]) -> Result<Self, PingError> {
+        let name = read_string(buf)?;
+        // Signature data, timestamp, public key and signature
+        if (protocol == MINECRAFT_1_19 || protocol == MINECRAFT_1_19_1) && read_bool(buf)? {
+            read_i64(buf)?;
+            read_byte_array(buf)?;
+            read_byte_array(buf)?;
+        }
+        let uuid = match protocol {
+            ..MINECRAFT_1_19_1 => None,
+            MINECRAFT_1_19_1..=MINECRAFT_1_20 => {
+                if read_bool(buf)? {
+                    Some(read_uuid(buf)?)
+                } else {
+                    None
+                }
+            }
+            _ => Some(read_uuid(buf)?),
+        };
+        Ok(LoginStart { name, uuid })
+    }
+}
+
+/// Encryption Request, sent by servers in online mode
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EncryptionRequest {
+    pub server_id: String,
+    /// DER encoded RSA public key
+    pub public_key: Vec<u8>,
+    pub verify_token: Vec<u8>,
+    /// Whether the client should authenticate with Mojang, sent since 1.20.5
+    pub should_authenticate: bool,
+}
+
+impl VersionedPacket for EncryptionRequest {
+    const ID: i32 = 0x01;
+
+    fn encode_for(&self, protocol: ProtocolNum, buf: &mut Vec<u8>) {
+        write_string(buf, &self.server_id);
+        if protocol < MINECRAFT_1_8 {
+            // 1.7 prefixes arrays with a short
+            write_u16(buf, self.public_key.len() as u16);
+            buf.extend_from_slice(&self.public_key);
+            write_u16(buf, self.verify_token.len() as u16);
+            buf.extend_from_slice(&self.verify_token);
+        } else {
+            write_byte_array(buf, &self.public_key);
+            write_byte_array(buf, &self.verify_token);
+        }
+        if protocol >= MINECRAFT_1_20_5 {
+            write_bool(buf, self.should_authenticate);
+        }
+    }
+
+    fn decode_for(protocol: ProtocolNum, buf: &mut &[u8]) -> Result<Self, PingError> {
+        let server_id = read_string(buf)?;
+        let (public_key, verify_token) = if protocol < MINECRAFT_1_8 {
+            let len = read_u16(buf)? as usize;
+            let public_key = take(buf, len)?.to_vec();
+            let len = read_u16(buf)? as usize;
+            (public_key, take(buf, len)?.to_vec())
+        } else {
+            (read_byte_array(buf)?, read_byte_array(buf)?)
+        };
+        let should_authenticate = protocol < MINECRAFT_1_20_5 || read_bool(buf)?;
+        Ok(EncryptionRequest { server_id, public_key, verify_token, should_authenticate })
+    }
+}
+
+/// Property of the player's profile, such as their skin
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoginProperty {
+    pub name: String,
+    pub value: String,
+    pub signature: Option<String>,
+}
+
+/// Login Success, sent by servers in offline mode right after Login Start
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoginSuccess {
+    pub uuid: u128,
+    pub name: String,
+    /// Sent since 1.19
+    pub properties: Vec<LoginProperty>,
+}
+
+impl VersionedPacket for LoginSuccess {
+    const ID: i32 = 0x02;
+
+    fn encode_for(&self, protocol: ProtocolNum, buf: &mut Vec<u8>) {
+        if protocol < MINECRAFT_1_16 {
+            write_string(buf, &format_uuid(self.uuid));
+        } else {
+            write_uuid(buf, self.uuid);
+        }
+        write_string(buf, &self.name);
+        if protocol >= MINECRAFT_1_19 {
+            write_varint(buf, self.properties.len() as i32);
+            for property in self.properties.iter() {
+                write_string(buf, &property.name);
+                write_string(buf, &property.value);
+                write_bool(buf, property.signature.is_some());
+                if let Some(signature) = &property.signature {
+                    write_string(buf, signature);
+                }
+            }
+        }
+        if protocol == MINECRAFT_1_20_5 || protocol == MINECRAFT_1_21 {
+            // Strict error handling, only sent by 1.20.5 to 1.21.1
+            write_bool(buf, false);
+        }
+    }
+
+    fn decode_for(protocol: ProtocolNum, buf: &mut &[u8]) -> Result<Self, PingError> {
+        let uuid = if protocol < MINECRAFT_1_16 {
+            parse_uuid(&read_string(buf)?)?
+        } else {
+            read_uuid(buf)?
+        };
+        let name = read_string(buf)?;
+        let mut properties = Vec::new();
+        if protocol >= MINECRAFT_1_19 {
+            for _ in 0..read_varint(buf)? {
+                let name = read_string(buf)?;
+                let value = read_string(buf)?;
+                let signature = if read_bool(buf)? { Some(read_string(buf)?) } else { None };
+                properties.push(LoginProperty { name, value, signature });
+            }
+        }
+        if protocol == MINECRAFT_1_20_5 || protocol == MINECRAFT_1_21 {
+            read_bool(buf)?;
+        }
+        Ok(LoginSuccess { uuid, name, properties })
+    }
+}
+
+/// Parses a UUID with or without hyphens
+pub fn parse_uuid(s: &str) -> Result<u128, PingError> {
+    let hex = s.replace('-', "");
+    if hex.len() != 32 {
+        return Err(PingError::MalformedUuid(s.to_string()));
+    }
+    u128::from_str_radix(&hex, 16).map_err(|_| PingError::MalformedUuid(s.to_string()))
+}
+
+/// Hyphenated UUID as sent in Login Success before 1.16
+pub fn format_uuid(uuid: u128) -> String {
+    let s = format!("{uuid:032x}");
+    format!("{}-{}-{}-{}-{}", &s[..8], &s[8..12], &s[12..16], &s[16..20], &s[20..])
+}
+
+/// Player data BungeeCord passes to backends in the handshake host with `ip_forward: true`
+#[derive(Clone, Debug, PartialEq)]
+pub struct BungeeForwarding {
+    /// Address the player connected to the proxy from
+    pub client_ip: IpAddr,
+    pub uuid: u128,
+    /// Profile properties, an array of `name`, `value` and `signature` objects, left out when `None`
+    pub properties: Option<Value>,
+}
+
+impl BungeeForwarding {
+    /// Handshake host as written by the proxy, `host\0clientIP\0uuid\0properties` with an unhyphenated UUID
+    pub fn forwarded_host(&self, host: &str) -> String {
+        let mut res = format!("{host}\0{}\0{:032x}", self.client_ip, self.uuid);
+        if let Some(properties) = &self.properties {
+            res.push('\0');
+            res.push_str(&properties.to_string());
+        }
+        res
+    }
+
+    /// Splits a forwarded handshake host into the original host and the player data, as backends do.
+    /// `None` for hosts not forwarded.
+    pub fn parse(host: &str) -> Option<(String, BungeeForwarding)> {
+        let parts: Vec<&str> = host.split('\0').collect();
+        if !(3..=4).contains(&parts.len()) {
+            return None;
+        }
+        let properties = match parts.get(3) {
+            Some(v) => Some(serde_json::from_str(v).ok()?),
+            None => None,
+        };
+        let forwarding = BungeeForwarding { client_ip: parts[1].parse().ok()?, uuid: parse_uuid(parts[2]).ok()?, properties };
+        Some((parts[0].to_string(), forwarding))
+    }
+}
+
+/// Set Compression, sent since 1.8 before Login Success
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SetCompression {
+    /// Smallest packet compressed, negative to disable compression
+    pub threshold: i32,
+}
+
+impl Packet for SetCompression {
+    const ID: i32 = 0x03;
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_varint(buf, self.threshold);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, PingError> {
+        Ok(SetCompression { threshold: read_varint(buf)? })
+    }
+}
+
+/// Login Plugin Request, sent since 1.13 by modded servers and proxies
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoginPluginRequest {
+    pub message_id: i32,
+    pub channel: String,
+    pub data: Vec<u8>,
+}
+
+impl Packet for LoginPluginRequest {
+    const ID: i32 = 0x04;
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_varint(buf, self.message_id);
+        write_string(buf, &self.channel);
+        buf.extend_from_slice(&self.data);
+    }
+
+    fn decode(buf: &mut &[u8]) -> Result<Self, PingError> {
+        let message_id = read_varint(buf)?;
+        let channel = read_string(buf)?;
+        let data = take(buf, buf.len())?.to_vec();
+        Ok(LoginPluginRequest { message_id, channel, data })
+    }
+}
+
+pub fn compose_handshake_packet(host: &str, port: u16, protocol: ProtocolNum, intent: Intent) -> Vec<u8> {
+    encode_packet(&Handshake { protocol, host: host.to_string(), port, next_state: intent })
+}
+
+pub fn compose_login_start_packet(protocol: ProtocolNum, name: &str, uuid: Option<u128>) -> Vec<u8> {
+    encode_versioned_packet(protocol, &LoginStart { name: name.to_string(), uuid })
 }
 
 pub fn compose_status_request_packet() -> Vec<u8> {
-    // Packet full length in varint. Packet ID.
-    vec![0x01, 0x00]
+    encode_packet(&StatusRequest)
+}
+
+pub fn compose_ping_request_packet(payload: i64) -> Vec<u8> {
+    encode_packet(&PingRequest { payload })
+}
+
+pub fn compose_status_response_packet(json: &str) -> Vec<u8> {
+    encode_packet(&StatusResponsePacket { json: json.to_string() })
+}
+
+pub fn compose_pong_response_packet(payload: i64) -> Vec<u8> {
+    encode_packet(&PongResponse { payload })
+}
+
+pub fn compose_login_disconnect_packet(reason_json: &str) -> Vec<u8> {
+    encode_packet(&LoginDisconnect { reason: reason_json.to_string() })
+}
+
+/// Parses a handshake packet, `content` starts at the packet ID
+pub fn parse_handshake_packet(content: &[u8]) -> Result<Handshake, PingError> {
+    decode_packet(content)
+}
+
+/// Returns the JSON of a Status Response packet, `content` starts at the packet ID
+pub fn parse_status_response_packet(content: &[u8]) -> Result<Vec<u8>, PingError> {
+    decode_packet::<StatusResponsePacket>(content).map(|v| v.json.into_bytes())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::protocol::{LATEST, MINECRAFT_1_8};
+    use crate::protocol::{LATEST, MINECRAFT_1_12_2, MINECRAFT_1_20_2, MINECRAFT_1_7_10};
 
     use super::*;
 
@@ -67,15 +704,89 @@ mod tests {
         assert_eq!(e, t);
     }
 
+    #[test]
+    fn test_varint() {
+        for (value, encoded) in [(0, vec![0x00]), (1, vec![0x01]), (127, vec![0x7F]), (128, vec![0x80, 0x01]), (25565, vec![0xDD, 0xC7, 0x01]),
+            (2147483647, vec![0xFF, 0xFF, 0xFF, 0xFF, 0x07]), (-1, vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F]), (-2147483648, vec![0x80, 0x80, 0x80, 0x80, 0x08])] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value);
+            assert_eq!(encoded, buf);
+            let mut t = encoded.as_slice();
+            assert_eq!(value, read_varint(&mut t).unwrap());
+            assert!(t.is_empty());
+        }
+        assert!(matches!(read_varint(&mut [0x80, 0x80].as_slice()), Err(PingError::MalformedVarInt)));
+        assert!(matches!(read_varint(&mut [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01].as_slice()), Err(PingError::MalformedVarInt)));
+    }
+
+    #[test]
+    fn test_varlong() {
+        for (value, encoded) in [(0, vec![0x00]), (2147483648, vec![0x80, 0x80, 0x80, 0x80, 0x08]), (9223372036854775807, vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F]),
+            (-1, vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01])] {
+            let mut buf = Vec::new();
+            write_varlong(&mut buf, value);
+            assert_eq!(encoded, buf);
+            assert_eq!(value, read_varlong(&mut encoded.as_slice()).unwrap());
+        }
+        assert!(matches!(read_varlong(&mut [0xFF; 11].as_slice()), Err(PingError::MalformedVarInt)));
+    }
+
+    #[test]
+    fn test_string_uuid_position() {
+        let mut buf = Vec::new();
+        write_string(&mut buf, "§aHi");
+        write_uuid(&mut buf, 0x069a79f444e94726a5befca90e38aaf5);
+        write_position(&mut buf, Position { x: -1, y: -64, z: 33554431 });
+        assert_eq!(vec![0x05, 0xC2, 0xA7, 0x61, 0x48, 0x69], buf[..6]);
+
+        let mut t = buf.as_slice();
+        assert_eq!("§aHi", read_string(&mut t).unwrap());
+        assert_eq!(0x069a79f444e94726a5befca90e38aaf5, read_uuid(&mut t).unwrap());
+        assert_eq!(Position { x: -1, y: -64, z: 33554431 }, read_position(&mut t).unwrap());
+        assert!(t.is_empty());
+
+        assert!(matches!(read_string(&mut [0x02, 0x41].as_slice()), Err(PingError::InvalidLength(2))));
+        assert!(matches!(read_string(&mut [0x01, 0xFF].as_slice()), Err(PingError::MalformedString)));
+        assert!(matches!(read_uuid(&mut [0x00; 15].as_slice()), Err(PingError::InvalidLength(16))));
+    }
+
+    #[tokio::test]
+    async fn test_frame_codec() {
+        let mut t = Vec::new();
+        write_packet(&mut t, Phase::Status, &StatusResponsePacket { json: "{}".to_string() }).await.unwrap();
+        write_packet(&mut t, Phase::Ping, &PongResponse { payload: 42 }).await.unwrap();
+
+        let mut reader = t.as_slice();
+        let res: StatusResponsePacket = read_packet(&mut reader, Phase::Status, 16).await.unwrap();
+        assert_eq!("{}", res.json);
+        let mut peek = reader;
+        assert!(matches!(read_packet::<_, StatusResponsePacket>(&mut peek, Phase::Ping, 16).await, Err(PingError::UnexpectedPacketId { .. })));
+        let mut peek = reader;
+        assert!(matches!(read_frame(&mut peek, Phase::Ping, 8).await, Err(PingError::OversizedFrame { size: 9, max: 8 })));
+        let res: PongResponse = read_packet(&mut reader, Phase::Ping, 16).await.unwrap();
+        assert_eq!(42, res.payload);
+        assert!(matches!(read_frame(&mut reader, Phase::Ping, 16).await, Err(PingError::Truncated(Phase::Ping))));
+        assert!(matches!(read_frame(&mut [0x05, 0x00].as_slice(), Phase::Status, 16).await, Err(PingError::Truncated(Phase::Status))));
+        assert!(matches!(read_frame(&mut [0x80; 6].as_slice(), Phase::Status, 16).await, Err(PingError::MalformedVarInt)));
+    }
+
     #[test]
     fn test_compose_handshake_packet() {
         let mut t: Vec<u8> = vec![0x00, 47, 9, 0x6C, 0x6F, 0x63, 0x61, 0x6C, 0x68, 0x6F, 0x73, 0x74, 0xFF, 0xFE, 0x01];
         packet_raw_content_add_varint_length(&mut t);
-        assert_eq!(t, compose_handshake_packet(&"localhost".to_string(), 65534, MINECRAFT_1_8));
+        assert_eq!(t, compose_handshake_packet("localhost", 65534, MINECRAFT_1_8, Intent::Status));
 
         let mut t: Vec<u8> = vec![0x00, 132, 6, 14, 0x6D, 0x63, 0x2E, 0x68, 0x79, 0x70, 0x69, 0x78, 0x65, 0x6C, 0x2E, 0x6E, 0x65, 0x74, 0x63, 0xDD, 0x01];
         packet_raw_content_add_varint_length(&mut t);
-        assert_eq!(t, compose_handshake_packet(&"mc.hypixel.net".to_string(), 25565, LATEST));
+        assert_eq!(t, compose_handshake_packet("mc.hypixel.net", 25565, LATEST, Intent::Status));
+
+        let mut t: Vec<u8> = vec![0x00, 47, 9, 0x6C, 0x6F, 0x63, 0x61, 0x6C, 0x68, 0x6F, 0x73, 0x74, 0xFF, 0xFE, 0x02];
+        packet_raw_content_add_varint_length(&mut t);
+        assert_eq!(t, compose_handshake_packet("localhost", 65534, MINECRAFT_1_8, Intent::Login));
+
+        let mut t: Vec<u8> = vec![0x00, 0xFE, 0x05, 9, 0x6C, 0x6F, 0x63, 0x61, 0x6C, 0x68, 0x6F, 0x73, 0x74, 0x63, 0xDD, 0x03];
+        packet_raw_content_add_varint_length(&mut t);
+        assert_eq!(t, compose_handshake_packet("localhost", 25565, MINECRAFT_1_20_5, Intent::Transfer));
     }
 
     #[test]
@@ -84,4 +795,110 @@ mod tests {
         packet_raw_content_add_varint_length(&mut t);
         assert_eq!(t, compose_status_request_packet());
     }
+
+    #[test]
+    fn test_ping_request_packet() {
+        let mut t: Vec<u8> = vec![0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2A];
+        packet_raw_content_add_varint_length(&mut t);
+        assert_eq!(t, compose_ping_request_packet(42));
+
+        let mut t: Vec<u8> = vec![0x01, 0x00, 0x00, 0x01, 0x97, 0xFF, 0xF0, 0xCB, 0x00];
+        packet_raw_content_add_varint_length(&mut t);
+        assert_eq!(t, compose_ping_request_packet(1752345660160));
+    }
+
+    #[test]
+    fn test_status_response_packet() {
+        let mut t: Vec<u8> = vec![0x00, 0x02, 0x7B, 0x7D];
+        packet_raw_content_add_varint_length(&mut t);
+        assert_eq!(t, compose_status_response_packet("{}"));
+    }
+
+    #[test]
+    fn test_parse_status_response_packet() {
+        let t = compose_status_response_packet("{}");
+        let res = parse_status_response_packet(&t[1..]);
+        assert!(res.is_ok());
+        assert_eq!(b"{}".to_vec(), res.unwrap());
+
+        assert!(matches!(parse_status_response_packet(&t[1..3]), Err(PingError::InvalidLength(2))));
+        assert!(matches!(parse_status_response_packet(&[0x00, 0x01, 0x7B, 0x7D]), Err(PingError::InvalidLength(1))));
+        assert!(matches!(parse_status_response_packet(&[0x00, 0x80]), Err(PingError::MalformedVarInt)));
+        assert!(matches!(parse_status_response_packet(&[0x01, 0x00]), Err(PingError::UnexpectedPacketId { .. })));
+    }
+
+    #[test]
+    fn test_bungee_forwarding() {
+        let forwarding = BungeeForwarding { client_ip: "203.0.113.7".parse().unwrap(), uuid: 0x069a79f444e94726a5befca90e38aaf5, properties: None };
+        let host = forwarding.forwarded_host("mc.example.com");
+        assert_eq!("mc.example.com\x00203.0.113.7\x00069a79f444e94726a5befca90e38aaf5", host);
+        assert_eq!(Some(("mc.example.com".to_string(), forwarding.clone())), BungeeForwarding::parse(&host));
+
+        let properties = serde_json::json!([{"name": "textures", "value": "e30=", "signature": "c2ln"}]);
+        let forwarding = BungeeForwarding { client_ip: "2001:db8::1".parse().unwrap(), properties: Some(properties), ..forwarding };
+        let host = forwarding.forwarded_host("localhost");
+        assert_eq!("localhost\x002001:db8::1\x00069a79f444e94726a5befca90e38aaf5\x00[{\"name\":\"textures\",\"signature\":\"c2ln\",\"value\":\"e30=\"}]", host);
+        assert_eq!(Some(("localhost".to_string(), forwarding)), BungeeForwarding::parse(&host));
+
+        assert_eq!(None, BungeeForwarding::parse("localhost"));
+        assert_eq!(None, BungeeForwarding::parse("localhost\0FML3\0"));
+        assert_eq!(None, BungeeForwarding::parse("localhost\x00203.0.113.7\x00player"));
+    }
+
+    #[test]
+    fn test_login_start_layouts() {
+        let start = LoginStart { name: "Notch".to_string(), uuid: Some(0x069a79f444e94726a5befca90e38aaf5) };
+        // Name, then signature and UUID fields depending on the protocol
+        for (protocol, extra) in [(MINECRAFT_1_8, 0), (MINECRAFT_1_19, 1), (MINECRAFT_1_19_1, 18), (MINECRAFT_1_19_3, 17), (MINECRAFT_1_20, 17), (MINECRAFT_1_20_2, 16), (LATEST, 16)] {
+            let packet = encode_versioned_packet(protocol, &start);
+            assert_eq!(1 + 1 + 6 + extra, packet.len(), "protocol {protocol}");
+            let decoded: LoginStart = decode_versioned_packet(protocol, &packet[1..]).unwrap();
+            assert_eq!(start.name, decoded.name);
+            assert_eq!(protocol >= MINECRAFT_1_19_1, decoded.uuid.is_some());
+        }
+        let packet = encode_versioned_packet(MINECRAFT_1_19_3, &LoginStart { name: "Notch".to_string(), uuid: None });
+        assert_eq!(vec![0x08, 0x00, 0x05, b'N', b'o', b't', b'c', b'h', 0x00], packet);
+    }
+
+    #[test]
+    fn test_login_replies() {
+        let request = EncryptionRequest { server_id: String::new(), public_key: vec![0x30; 162], verify_token: vec![1, 2, 3, 4], should_authenticate: true };
+        for protocol in [MINECRAFT_1_7_10, MINECRAFT_1_8, MINECRAFT_1_20_5] {
+            let packet = encode_versioned_packet(protocol, &request);
+            assert_eq!(request, decode_versioned_packet(protocol, &packet[2..]).unwrap());
+        }
+        assert_eq!(Some(&0x00), encode_versioned_packet(MINECRAFT_1_7_10, &request).get(4));
+
+        let success = LoginSuccess { uuid: 0x069a79f444e94726a5befca90e38aaf5, name: "Notch".to_string(), properties: Vec::new() };
+        let packet = encode_versioned_packet(MINECRAFT_1_12_2, &success);
+        assert_eq!(b"069a79f4-44e9-4726-a5be-fca90e38aaf5", &packet[3..39]);
+        for protocol in [MINECRAFT_1_12_2, MINECRAFT_1_16, MINECRAFT_1_21, LATEST] {
+            let packet = encode_versioned_packet(protocol, &success);
+            assert_eq!(success, decode_versioned_packet(protocol, &packet[1..]).unwrap());
+        }
+
+        assert_eq!(0x069a79f444e94726a5befca90e38aaf5, parse_uuid("069a79f444e94726a5befca90e38aaf5").unwrap());
+        assert!(parse_uuid("069a79f4").is_err());
+        assert!(parse_uuid("069a79f4-44e9-4726-a5be-fca90e38aafz").is_err());
+
+        let packet = encode_packet(&SetCompression { threshold: 256 });
+        assert_eq!(vec![0x03, 0x03, 0x80, 0x02], packet);
+    }
+
+    #[test]
+    fn test_parse_handshake_packet() {
+        let t = compose_handshake_packet("mc.hypixel.net", 25565, LATEST, Intent::Status);
+        let res = parse_handshake_packet(&t[1..]);
+        assert!(res.is_ok());
+        assert_eq!(Handshake { protocol: LATEST, host: "mc.hypixel.net".to_string(), port: 25565, next_state: Intent::Status }, res.unwrap());
+
+        assert!(matches!(parse_handshake_packet(&[0x01]), Err(PingError::UnexpectedPacketId { .. })));
+        assert!(matches!(parse_handshake_packet(&t[1..t.len() - 3]), Err(PingError::InvalidLength(_))));
+        assert!(matches!(parse_handshake_packet(&t[1..t.len() - 1]), Err(PingError::MalformedVarInt)));
+        assert!(matches!(parse_handshake_packet(&[0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]), Err(PingError::MalformedVarInt)));
+
+        let mut t = t[1..].to_vec();
+        *t.last_mut().unwrap() = 0x04;
+        assert!(matches!(parse_handshake_packet(&t), Err(PingError::UnknownIntent(4))));
+    }
 }
\ No newline at end of file
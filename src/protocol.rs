@@ -1,19 +1,36 @@
 #![allow(unused)]
 
-use std::io::{Read, Write};
-use std::net::{Shutdown, SocketAddr, TcpStream};
-use std::slice;
+use std::future::Future;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::sync::OnceLock;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use im::hashset::HashSet;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time;
+
+use crate::dns::{IpMode, try_resolved, unbracket};
+use crate::error::{Phase, PingError};
+use crate::forge::ClientFlavor;
+use crate::haproxy::ProxyHeader;
+use crate::legacy::{legacy_ping, LegacyVariant};
+use crate::packet::{BungeeForwarding, Handshake, Intent, PingRequest, PongResponse, read_packet, StatusRequest, StatusResponsePacket, write_packet};
+use crate::proxy::{connect_through, Proxy};
+use crate::status::{StatusResponse, StatusResult};
+use crate::version::VersionRegistry;
+
+/// Protocol number sent in the handshake, signed as snapshots set bit 30
+pub type ProtocolNum = i32;
 
-use crate::dns::resolve;
-use crate::packet::{compose_handshake_packet, compose_status_request_packet};
+pub const DEFAULT_PORT: u16 = 25565;
 
-pub type ProtocolNum = u16;
+/// Largest packet the vanilla client accepts, the maximum of a 3 byte varint
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 2097151;
 
-pub const DEFAULT_PORT: u16 = 25565;
+/// Packet ID and payload of the Pong Response
+const MAX_PONG_SIZE: usize = 9;
 
 pub const MINECRAFT_1_7: ProtocolNum = 3;
 pub const MINECRAFT_1_7_1: ProtocolNum = 3;
@@ -111,168 +128,428 @@ pub const MINECRAFT_1_21_7: ProtocolNum = 772;
 pub const MINECRAFT_1_21_8: ProtocolNum = 772;
 pub const LATEST: ProtocolNum = MINECRAFT_1_21_8;
 
+/// Set in the protocol number of every snapshot, pre-release and release candidate since 1.16.4-pre1
+pub const SNAPSHOT_PROTOCOL_BIT: ProtocolNum = 0x40000000;
+
+/// Protocol number of the `n`th snapshot since 1.16.4-pre1, which is the first
+pub const fn snapshot_protocol(n: i32) -> ProtocolNum {
+    SNAPSHOT_PROTOCOL_BIT | n
+}
+
+/// Snapshots numbered so far stay well below this, larger numbers are not taken for snapshots
+pub const MAX_SNAPSHOT_NUMBER: i32 = 0x1000;
+
+pub fn is_snapshot_protocol_number(n: ProtocolNum) -> bool {
+    n > SNAPSHOT_PROTOCOL_BIT && n - SNAPSHOT_PROTOCOL_BIT < MAX_SNAPSHOT_NUMBER
+}
+
 pub fn is_known_protocol_number(n: ProtocolNum) -> bool {
-    matches!(n, MINECRAFT_1_7_1
-            | MINECRAFT_1_7_5
-            | MINECRAFT_1_7_10
-            | MINECRAFT_1_8_9
-            | MINECRAFT_1_9
-            | MINECRAFT_1_9_1
-            | MINECRAFT_1_9_2
-            | MINECRAFT_1_9_4
-            | MINECRAFT_1_10_2
-            | MINECRAFT_1_11
-            | MINECRAFT_1_11_2
-            | MINECRAFT_1_12
-            | MINECRAFT_1_12_1
-            | MINECRAFT_1_12_2
-            | MINECRAFT_1_13
-            | MINECRAFT_1_13_1
-            | MINECRAFT_1_13_2
-            | MINECRAFT_1_14
-            | MINECRAFT_1_14_1
-            | MINECRAFT_1_14_2
-            | MINECRAFT_1_14_3
-            | MINECRAFT_1_14_4
-            | MINECRAFT_1_15
-            | MINECRAFT_1_15_1
-            | MINECRAFT_1_15_2
-            | MINECRAFT_1_16
-            | MINECRAFT_1_16_1
-            | MINECRAFT_1_16_2
-            | MINECRAFT_1_16_3
-            | MINECRAFT_1_16_5
-            | MINECRAFT_1_17
-            | MINECRAFT_1_17_1
-            | MINECRAFT_1_18_1
-            | MINECRAFT_1_18_2
-            | MINECRAFT_1_19
-            | MINECRAFT_1_19_2
-            | MINECRAFT_1_19_3
-            | MINECRAFT_1_19_4
-            | MINECRAFT_1_20_1
-            | MINECRAFT_1_20_2
-            | MINECRAFT_1_20_4
-            | MINECRAFT_1_20_6
-            | MINECRAFT_1_21_1
-            | MINECRAFT_1_21_3
-            | MINECRAFT_1_21_4
-            | MINECRAFT_1_21_5
-            | MINECRAFT_1_21_6
-            | MINECRAFT_1_21_8
-    )
+    VersionRegistry::current().is_known(n)
 }
 
-pub async fn ping(host: &String, port: u16, fakehost: &String, protocol: ProtocolNum, timeout: u8) -> Result<Vec<u8>, String> {
-    if host.is_empty() {
-        return Err("Invalid host string".to_string());
+#[derive(Clone, Debug)]
+pub struct PingOptions {
+    /// Host sent in the handshake instead of the resolved one, if not empty
+    pub fakehost: String,
+    pub protocol: ProtocolNum,
+    /// Timeout in seconds, applied to each connection attempt
+    pub timeout: u8,
+    pub ip_mode: IpMode,
+    /// Use the pre-netty server list ping instead of the handshake
+    pub legacy: Option<LegacyVariant>,
+    /// Retry with the legacy ping when the server does not answer the handshake properly
+    pub legacy_fallback: bool,
+    /// Largest Status Response packet accepted, in bytes
+    pub max_response_size: usize,
+    /// Send the protocol number even if it is not a known release, snapshot or pre-release
+    pub allow_unknown_protocol: bool,
+    /// Ping again with the protocol number advertised by the server if it differs from `protocol`,
+    /// for proxies answering differently depending on the client version
+    pub auto_protocol: bool,
+    /// Client announced in the handshake host, some modded servers only list their mods to Forge clients
+    pub client_flavor: ClientFlavor,
+    /// Player data added to the handshake host like BungeeCord does, for backends expecting IP forwarding
+    pub forwarding: Option<BungeeForwarding>,
+    /// PROXY protocol header written right after connecting, for servers with `proxy-protocol: true`
+    pub proxy_protocol: Option<ProxyHeader>,
+    /// SOCKS5 or HTTP proxy to connect through instead of connecting directly
+    pub proxy: Option<Proxy>,
+}
+
+impl PingOptions {
+    pub(crate) fn timeout_duration(&self) -> Duration {
+        Duration::from_secs(self.timeout as u64)
     }
-    if !is_known_protocol_number(protocol) {
-        return Err("Unknown protocol number".to_string());
+
+    /// Refuses a protocol number that is not a known release, snapshot or pre-release unless `allow_unknown_protocol` is set
+    pub(crate) fn check_protocol(&self) -> Result<(), PingError> {
+        if !self.allow_unknown_protocol && !is_known_protocol_number(self.protocol) {
+            return Err(PingError::UnknownProtocol(self.protocol));
+        }
+        Ok(())
     }
-    if timeout == 0 {
-        return Err("Timeout in seconds must be bigger than 0".to_string());
+}
+
+impl Default for PingOptions {
+    fn default() -> Self {
+        PingOptions {
+            fakehost: String::new(),
+            protocol: MINECRAFT_1_8,
+            timeout: 5,
+            ip_mode: IpMode::default(),
+            legacy: None,
+            legacy_fallback: false,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            allow_unknown_protocol: false,
+            auto_protocol: false,
+            client_flavor: ClientFlavor::default(),
+            forwarding: None,
+            proxy_protocol: None,
+            proxy: None,
+        }
     }
+}
 
-    let host_touse = if fakehost.is_empty() {
-        String::from(host)
-    } else {
-        String::from(fakehost)
-    };
+pub async fn ping(host: &str, port: u16, fakehost: &String, protocol: ProtocolNum, timeout: u8) -> Result<Vec<u8>, PingError> {
+    let options = PingOptions { fakehost: String::from(fakehost), protocol, timeout, ..Default::default() };
+    let (res, _) = status_exchange(host, port, &options).await?;
+    Ok(res)
+}
 
-    let port_touse = if port == 0 {
-        DEFAULT_PORT
+pub async fn ping_status(host: &str, port: u16, fakehost: &String, protocol: ProtocolNum, timeout: u8) -> Result<StatusResult, PingError> {
+    let options = PingOptions { fakehost: String::from(fakehost), protocol, timeout, ..Default::default() };
+    ping_with_options(host, port, &options).await
+}
+
+pub async fn ping_with_options(host: &str, port: u16, options: &PingOptions) -> Result<StatusResult, PingError> {
+    if let Some(variant) = options.legacy {
+        return legacy_ping(host, port, variant, options).await;
+    }
+    let mut res = status_ping(host, port, options).await;
+    if options.auto_protocol {
+        let advertised = res.as_ref().ok().and_then(|v| v.advertised_protocol);
+        if let Some(protocol) = advertised.filter(|v| *v > 0 && *v != options.protocol) {
+            // The server named the number itself, it does not have to be known
+            let options = PingOptions { protocol, allow_unknown_protocol: true, ..options.clone() };
+            res = status_ping(host, port, &options).await;
+        }
+    }
+    match res {
+        Err(e) if options.legacy_fallback && e.is_protocol_error() => {
+            legacy_ping(host, port, LegacyVariant::V1_6, options).await.map_err(|_| e)
+        }
+        res => res,
+    }
+}
+
+async fn status_ping(host: &str, port: u16, options: &PingOptions) -> Result<StatusResult, PingError> {
+    let (raw, latency) = status_exchange(host, port, options).await?;
+    let response = StatusResponse::from_slice(&raw)?;
+    let advertised_protocol = response.version.as_ref().map(|v| v.protocol);
+    Ok(StatusResult { response, raw, latency, legacy: None, requested_protocol: Some(options.protocol), advertised_protocol })
+}
+
+async fn status_exchange(host: &str, port: u16, options: &PingOptions) -> Result<(Vec<u8>, Option<Duration>), PingError> {
+    let protocol = options.protocol;
+    options.check_protocol()?;
+
+    let host_touse = handshake_host(host, options);
+    let port_touse = port_or_default(port);
+    let mut stream = open_stream(host, port_touse, options).await?;
+    let res = exchange_on(&mut stream, options.timeout_duration(), &host_touse, port_touse, protocol, options.max_response_size).await;
+    shutoff(&mut stream).await;
+    res
+}
+
+pub(crate) fn handshake_host(host: &str, options: &PingOptions) -> String {
+    let host = if options.fakehost.is_empty() { unbracket(host) } else { &options.fakehost };
+    let host = format!("{host}{}", options.client_flavor.host_marker(options.protocol));
+    match &options.forwarding {
+        Some(forwarding) => forwarding.forwarded_host(&host),
+        None => host,
+    }
+}
+
+pub(crate) fn port_or_default(port: u16) -> u16 {
+    port_or(port, DEFAULT_PORT)
+}
+
+/// The port, or `default` when it is 0
+pub(crate) fn port_or(port: u16, default: u16) -> u16 {
+    if port == 0 {
+        default
     } else {
         port
-    };
+    }
+}
 
-    let ips = resolve(host.as_str(), &port_touse).await?;
+/// Resolves the host and connects to the first address accepting the connection
+pub(crate) async fn open_stream(host: &str, port: u16, options: &PingOptions) -> Result<TcpStream, PingError> {
+    if host.is_empty() {
+        return Err(PingError::InvalidHost(host.to_string()));
+    }
+    if options.timeout == 0 {
+        return Err(PingError::InvalidTimeout);
+    }
 
-    for v in ips.iter() {
-        let dur = Duration::from_secs(timeout as u64);
+    if let (Some(proxy), Some(header)) = (&options.proxy, &options.proxy_protocol) {
+        if proxy.resolves_remotely(host) && header.destination.is_none() {
+            return Err(PingError::InvalidProxyRequest("the proxy resolves the host, the PROXY header needs an explicit destination".to_string()));
+        }
+    }
 
-        let stream = TcpStream::connect_timeout(&SocketAddr::from(*v), dur);
-        if stream.is_err() {
-            continue
+    let (mut stream, target) = match &options.proxy {
+        Some(proxy) => connect_through(proxy, host, port, options).await?,
+        None => {
+            let stream = connect_host(host, port, options.ip_mode, options.timeout_duration()).await?;
+            let peer = stream.peer_addr().map_err(|e| PingError::io(Phase::Connect, e))?;
+            (stream, Some(peer))
         }
-        let mut stream = stream.unwrap();
-        stream.set_read_timeout(Some(dur));
-        stream.set_write_timeout(Some(dur));
+    };
+    if let Some(header) = &options.proxy_protocol {
+        // Checked above, only a proxy resolving the host leaves the server address unknown
+        let Some(destination) = header.destination.or(target) else {
+            return Err(PingError::InvalidProxyRequest("the PROXY header needs an explicit destination".to_string()));
+        };
+        send_proxy_header(&mut stream, header, destination, options.timeout_duration()).await?;
+    }
+    Ok(stream)
+}
+
+/// Plain TCP connection to the first resolved address accepting it, shared by everything speaking TCP
+pub(crate) async fn connect_host(host: &str, port: u16, ip_mode: IpMode, dur: Duration) -> Result<TcpStream, PingError> {
+    try_resolved(host, port, ip_mode, |addr| async move { connect(&addr, dur).await }).await
+}
 
-        stream.write_all(&compose_handshake_packet(&host_touse, port_touse, protocol)).expect("Cannot perform handshake");
-        stream.write_all(&compose_status_request_packet()).expect("Cannot send status request");
+/// `destination` is the game server, which is not the peer when connected through a proxy
+async fn send_proxy_header(stream: &mut TcpStream, header: &ProxyHeader, destination: SocketAddr, dur: Duration) -> Result<(), PingError> {
+    let local = stream.local_addr().map_err(|e| PingError::io(Phase::Connect, e))?;
+    with_timeout(dur, Phase::Connect, stream.write_all(&header.compose(local, destination))).await
+}
 
-        let mut byte = 0u8;
+pub(crate) async fn connect(addr: &SocketAddr, dur: Duration) -> Result<TcpStream, PingError> {
+    match time::timeout(dur, TcpStream::connect(addr)).await {
+        Ok(Ok(stream)) => Ok(stream),
+        Ok(Err(e)) if e.kind() == io::ErrorKind::ConnectionRefused => Err(PingError::ConnectionRefused(*addr)),
+        Ok(Err(e)) => Err(PingError::Connect(*addr, e)),
+        Err(_) => Err(PingError::Timeout(Phase::Connect)),
+    }
+}
 
-        let resize = read_varint(&mut stream);
-        if resize.is_err() {
-            return Err(resize.err().unwrap().to_string());
-        }
+/// UDP socket on an unspecified local address of the same family, connected to `addr`
+pub(crate) async fn connect_udp(addr: &SocketAddr) -> Result<UdpSocket, PingError> {
+    let bind_addr = if addr.is_ipv4() {
+        SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+    } else {
+        SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+    };
+    let socket = UdpSocket::bind(bind_addr).await.map_err(|e| PingError::Connect(*addr, e))?;
+    socket.connect(addr).await.map_err(|e| PingError::Connect(*addr, e))?;
+    Ok(socket)
+}
 
-        stream.read_exact(slice::from_mut(&mut byte)).expect("Cannot read packet ID");
-        if byte != 0u8 {
-            shutoff(&stream);
-            return Err(format!("Unknown packet ID {byte}"));
-        }
+async fn exchange_on(stream: &mut TcpStream, dur: Duration, host: &str, port: u16, protocol: ProtocolNum, max: usize) -> Result<(Vec<u8>, Option<Duration>), PingError> {
+    let handshake = Handshake { protocol, host: host.to_string(), port, next_state: Intent::Status };
+    with_timeout(dur, Phase::Handshake, write_packet(stream, Phase::Handshake, &handshake)).await?;
+    with_timeout(dur, Phase::Handshake, write_packet(stream, Phase::Handshake, &StatusRequest)).await?;
 
-        let resize = read_varint(&mut stream);
-        if resize.is_err() {
-            return Err(resize.err().unwrap().to_string());
-        }
-        let resize = resize.unwrap();
-        let mut res: Vec<u8> = Vec::new();
-        let mut buf = [0; 4096];
-        let mut bytes_read = 0;
-        while bytes_read < resize {
-            let read_res = stream.read(&mut buf);
-            if read_res.is_err() {
-                shutoff(&stream);
-                return Err(read_res.err().unwrap().to_string());
-            }
-            let read_res = read_res.unwrap();
-            bytes_read += read_res;
-            res.extend_from_slice(&buf[..read_res]);
-        }
-        shutoff(&stream);
-        return Ok(res);
+    let res: StatusResponsePacket = with_timeout(dur, Phase::Status, read_packet(stream, Phase::Status, max)).await?;
+
+    // Some servers and proxies never answer the Ping Request, the status is kept without a latency
+    let latency = ping_pong(stream, dur).await.ok();
+    Ok((res.json.into_bytes(), latency))
+}
+
+async fn ping_pong(stream: &mut TcpStream, dur: Duration) -> Result<Duration, PingError> {
+    let payload = ping_payload();
+    let sent_at = Instant::now();
+    with_timeout(dur, Phase::Ping, write_packet(stream, Phase::Ping, &PingRequest { payload })).await?;
+    let pong: PongResponse = with_timeout(dur, Phase::Ping, read_packet(stream, Phase::Ping, MAX_PONG_SIZE)).await?;
+    if pong.payload != payload {
+        return Err(PingError::PongMismatch { sent: payload, received: pong.payload });
     }
-    Err("All IP addresses tried".to_string())
+    Ok(sent_at.elapsed())
 }
 
-fn read_varint(stream: &mut TcpStream) -> Result<usize, &'static str> {
-    let mut byte = 0x00;
-    let mut res = 0i32;
-    for i in 0.. {
-        if i > 5 {
-            shutoff(stream);
-            return Err("Malformed response, not a valid varint");
-        }
-        let buf = slice::from_mut(&mut byte);
-        #[allow(clippy::unused_io_amount)]
-        stream.read(buf).expect("Cannot read varint");
-        if buf.is_empty() {
-            break;
-        }
-        res |= (((buf[0] as i32) & 0x7Fi32) << (7 * i));
-        if ((buf[0] as i32) & 0x80i32) == 0 {
-            break
-        }
+/// Error of a future run by [`with_timeout`], made a `PingError` of the phase
+pub(crate) trait PhaseError {
+    fn in_phase(self, phase: Phase) -> PingError;
+}
+
+impl PhaseError for io::Error {
+    fn in_phase(self, phase: Phase) -> PingError {
+        PingError::io(phase, self)
     }
-    if res <= 0 {
-        shutoff(stream);
-        return Err("Malformed response, varint not bigger than 0");
+}
+
+impl PhaseError for PingError {
+    fn in_phase(self, _phase: Phase) -> PingError {
+        self
+    }
+}
+
+pub(crate) async fn with_timeout<T, E: PhaseError>(dur: Duration, phase: Phase, fut: impl Future<Output = Result<T, E>>) -> Result<T, PingError> {
+    match time::timeout(dur, fut).await {
+        Ok(res) => res.map_err(|e| e.in_phase(phase)),
+        Err(_) => Err(PingError::Timeout(phase)),
     }
-    Ok(res as usize)
 }
 
-fn shutoff(stream: &TcpStream) {
-    stream.shutdown(Shutdown::Both);
+fn ping_payload() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or_default()
+}
+
+pub(crate) async fn shutoff(stream: &mut TcpStream) {
+    stream.shutdown().await;
 }
 
 #[cfg(test)]
 mod tests {
+    use tokio::net::TcpListener;
+
+    use crate::haproxy::ProxyVersion;
+    use crate::testing::{MockFault, MockScript, MockServer, MockStatusScript};
+
     use super::*;
 
+    async fn ping_with_fault(fault: MockFault, timeout: u8) -> Result<Vec<u8>, PingError> {
+        let server = MockServer::start(MockStatusScript { fault: Some(fault), ..Default::default() }).await;
+        ping("127.0.0.1", server.port(), &String::new(), MINECRAFT_1_8, timeout).await
+    }
+
+    #[tokio::test]
+    async fn test_allow_unknown_protocol() {
+        let server = MockServer::start(MockScript::default()).await;
+        let mut options = PingOptions { protocol: LATEST + 1, timeout: 1, ..Default::default() };
+        assert!(matches!(ping_with_options("127.0.0.1", server.port(), &options).await, Err(PingError::UnknownProtocol(_))));
+        assert!(server.handshakes().is_empty());
+
+        options.allow_unknown_protocol = true;
+        assert!(ping_with_options("127.0.0.1", server.port(), &options).await.is_ok());
+        options.protocol = snapshot_protocol(0x105);
+        options.allow_unknown_protocol = false;
+        assert!(ping_with_options("127.0.0.1", server.port(), &options).await.is_ok());
+        let protocols: Vec<ProtocolNum> = server.handshakes().iter().map(|v| v.protocol).collect();
+        assert_eq!(vec![LATEST + 1, snapshot_protocol(0x105)], protocols);
+    }
+
+    #[tokio::test]
+    async fn test_auto_protocol() {
+        let server = MockServer::start(MockStatusScript {
+            json: r#"{"version":{"name":"Velocity 3.4.0","protocol":772},"description":"A proxy"}"#.to_string(),
+            ..Default::default()
+        }).await;
+        let options = PingOptions { timeout: 1, ..Default::default() };
+        let res = ping_with_options("127.0.0.1", server.port(), &options).await.unwrap();
+        assert_eq!(Some(MINECRAFT_1_8), res.requested_protocol);
+        assert_eq!(Some(MINECRAFT_1_21_8), res.advertised_protocol);
+
+        let options = PingOptions { auto_protocol: true, ..options };
+        let res = ping_with_options("127.0.0.1", server.port(), &options).await.unwrap();
+        assert_eq!(Some(MINECRAFT_1_21_8), res.requested_protocol);
+        assert_eq!(Some(MINECRAFT_1_21_8), res.advertised_protocol);
+        let protocols: Vec<ProtocolNum> = server.handshakes().iter().map(|v| v.protocol).collect();
+        assert_eq!(vec![MINECRAFT_1_8, MINECRAFT_1_8, MINECRAFT_1_21_8], protocols);
+
+        // Nothing to negotiate when the server already speaks the requested protocol
+        let options = PingOptions { protocol: MINECRAFT_1_21_8, ..options };
+        assert!(ping_with_options("127.0.0.1", server.port(), &options).await.is_ok());
+        assert_eq!(4, server.handshakes().len());
+    }
+
+    #[tokio::test]
+    async fn test_forge_client_flavor() {
+        let server = MockServer::start(MockScript::default()).await;
+        let options = PingOptions { protocol: MINECRAFT_1_12_2, timeout: 1, client_flavor: ClientFlavor::Forge, ..Default::default() };
+        assert!(ping_with_options("127.0.0.1", server.port(), &options).await.is_ok());
+        let options = PingOptions { protocol: MINECRAFT_1_20_1, fakehost: "mc.example.com".to_string(), ..options };
+        assert!(ping_with_options("127.0.0.1", server.port(), &options).await.is_ok());
+        let hosts: Vec<String> = server.handshakes().into_iter().map(|v| v.host).collect();
+        assert_eq!(vec!["127.0.0.1\0FML\0".to_string(), "mc.example.com\0FML3\0".to_string()], hosts);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_protocol() {
+        let server = MockServer::start(MockScript { proxy_protocol: true, ..Default::default() }).await;
+        let options = PingOptions { timeout: 1, ..Default::default() };
+        assert!(ping_with_options("127.0.0.1", server.port(), &options).await.is_err());
+
+        let header = ProxyHeader { source: Some("198.51.100.7:51234".parse().unwrap()), ..Default::default() };
+        let options = PingOptions { proxy_protocol: Some(header), ..options };
+        assert!(ping_with_options("127.0.0.1", server.port(), &options).await.is_ok());
+        let options = PingOptions { proxy_protocol: Some(ProxyHeader { version: ProxyVersion::V1, ..Default::default() }), ..options };
+        assert!(ping_with_options("127.0.0.1", server.port(), &options).await.is_ok());
+
+        let headers = server.proxy_headers();
+        assert_eq!(2, headers.len());
+        assert_eq!(Some("198.51.100.7:51234".parse().unwrap()), headers[0].source);
+        assert_eq!(Some(server.addr()), headers[0].destination);
+        assert_eq!(ProxyVersion::V1, headers[1].version);
+        assert_eq!(Some(server.addr()), headers[1].destination);
+        assert_eq!(2, server.handshakes().len());
+    }
+
+    #[tokio::test]
+    async fn test_max_response_size() {
+        let server = MockServer::start(MockScript::default()).await;
+        let status_len = MockStatusScript::default().json.len();
+
+        let options = PingOptions { max_response_size: status_len, timeout: 1, ..Default::default() };
+        let res = ping_with_options("127.0.0.1", server.port(), &options).await;
+        assert!(matches!(res, Err(PingError::OversizedFrame { max, .. }) if max == status_len));
+
+        let options = PingOptions { max_response_size: status_len + 2, timeout: 1, ..Default::default() };
+        assert!(ping_with_options("127.0.0.1", server.port(), &options).await.is_ok());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_ping_does_not_block_runtime() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let _server = tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((conn, _)) = listener.accept().await {
+                held.push(conn);
+            }
+        });
+
+        let started = Instant::now();
+        let pings: Vec<_> = (0..50).map(|_| tokio::spawn(async move {
+            ping("127.0.0.1", port, &String::new(), MINECRAFT_1_8, 1).await
+        })).collect();
+        for p in pings {
+            assert!(p.await.unwrap().is_err());
+        }
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_ping_errors() {
+        assert!(matches!(ping("", DEFAULT_PORT, &String::new(), MINECRAFT_1_8, 1).await, Err(PingError::InvalidHost(_))));
+        assert!(matches!(ping("127.0.0.1", DEFAULT_PORT, &String::new(), LATEST + 1, 1).await, Err(PingError::UnknownProtocol(_))));
+        assert!(matches!(ping("127.0.0.1", DEFAULT_PORT, &String::new(), MINECRAFT_1_8, 0).await, Err(PingError::InvalidTimeout)));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let res = ping("127.0.0.1", port, &String::new(), MINECRAFT_1_8, 1).await;
+        assert!(matches!(res, Err(PingError::ConnectionRefused(_))));
+        assert!(res.unwrap_err().is_unreachable());
+    }
+
+    #[tokio::test]
+    async fn test_ping_faults() {
+        assert!(matches!(ping_with_fault(MockFault::StallMidFrame, 1).await, Err(PingError::Timeout(Phase::Status))));
+        assert!(matches!(ping_with_fault(MockFault::PacketId(0x05), 1).await, Err(PingError::UnexpectedPacketId { expected: 0x00, actual: 0x05 })));
+        assert!(matches!(ping_with_fault(MockFault::CloseAfterHandshake, 1).await, Err(PingError::Truncated(Phase::Status))));
+        assert!(matches!(ping_with_fault(MockFault::TruncateAndClose, 1).await, Err(PingError::Truncated(Phase::Status))));
+        assert!(matches!(ping_with_fault(MockFault::OverlongVarInt, 1).await, Err(PingError::MalformedVarInt)));
+
+        let res = ping_with_fault(MockFault::Drip(Duration::from_millis(1)), 1).await;
+        assert!(res.is_ok());
+        assert_eq!(MockStatusScript::default().json.into_bytes(), res.unwrap());
+    }
+
     #[test]
     fn test_known_protocol_number() {
         assert!(!is_known_protocol_number(MINECRAFT_1_7 - 1));
@@ -355,5 +632,12 @@ mod tests {
         assert!(is_known_protocol_number(MINECRAFT_1_21_7));
         assert!(is_known_protocol_number(MINECRAFT_1_21_8));
         assert!(!is_known_protocol_number(LATEST + 1));
+        assert!(is_known_protocol_number(snapshot_protocol(1)));
+        assert!(is_known_protocol_number(snapshot_protocol(0x105)));
+        assert!(!is_known_protocol_number(MINECRAFT_1_8 + 1));
+        assert!(!is_known_protocol_number(SNAPSHOT_PROTOCOL_BIT));
+        assert!(!is_known_protocol_number(snapshot_protocol(MAX_SNAPSHOT_NUMBER)));
+        assert!(!is_known_protocol_number(0x7FFFFFFF));
+        assert!(!is_known_protocol_number(-1));
     }
 }
@@ -0,0 +1,382 @@
+#![allow(unused)]
+
+use std::io;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tokio::net::UdpSocket;
+use tokio::time;
+
+use crate::dns::try_resolved;
+use crate::error::{Phase, PingError};
+use crate::protocol::{connect_udp, PingOptions, port_or_default};
+
+const QUERY_MAGIC: [u8; 2] = [0xFE, 0xFD];
+const QUERY_HANDSHAKE: u8 = 0x09;
+const QUERY_STAT: u8 = 0x00;
+
+/// Padding preceding the key/value section of a full stat response
+const FULL_STAT_KV_PADDING: [u8; 11] = *b"splitnum\x00\x80\x00";
+/// Padding preceding the player section of a full stat response
+const FULL_STAT_PLAYER_PADDING: [u8; 10] = *b"\x01player_\x00\x00";
+
+/// Result of a basic stat request
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct QueryBasicStat {
+    pub motd: String,
+    pub gametype: String,
+    pub map: String,
+    pub online_players: i64,
+    pub max_players: i64,
+    pub host_port: u16,
+    pub host_ip: String,
+}
+
+/// Result of a full stat request
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct QueryFullStat {
+    pub motd: String,
+    pub gametype: String,
+    pub game_id: String,
+    pub version: String,
+    /// Server software, e.g. `CraftBukkit on Bukkit 1.21.8-R0.1-SNAPSHOT`
+    pub server_mod: Option<String>,
+    pub plugins: Vec<String>,
+    pub map: String,
+    pub online_players: i64,
+    pub max_players: i64,
+    pub host_port: u16,
+    pub host_ip: String,
+    pub players: Vec<String>,
+    /// Every key/value pair as received, including the ones above
+    pub raw: Vec<(String, String)>,
+}
+
+fn query_header(packet_type: u8, session_id: i32) -> Vec<u8> {
+    let mut content: Vec<u8> = Vec::with_capacity(15);
+    content.extend_from_slice(&QUERY_MAGIC);
+    content.push(packet_type);
+    content.extend_from_slice(&session_id.to_be_bytes());
+    content
+}
+
+pub fn compose_query_handshake_packet(session_id: i32) -> Vec<u8> {
+    query_header(QUERY_HANDSHAKE, session_id)
+}
+
+pub fn compose_basic_stat_packet(session_id: i32, challenge_token: i32) -> Vec<u8> {
+    let mut content = query_header(QUERY_STAT, session_id);
+    content.extend_from_slice(&challenge_token.to_be_bytes());
+    content
+}
+
+pub fn compose_full_stat_packet(session_id: i32, challenge_token: i32) -> Vec<u8> {
+    let mut content = compose_basic_stat_packet(session_id, challenge_token);
+    content.extend_from_slice(&[0x00; 4]);
+    content
+}
+
+/// Checks the type and session ID of a response and returns its payload
+fn response_payload(packet: &[u8], packet_type: u8, session_id: i32) -> Result<&[u8], PingError> {
+    if packet.len() < 5 {
+        return Err(malformed(packet));
+    }
+    if packet[0] != packet_type {
+        return Err(PingError::UnexpectedPacketId { expected: packet_type as i32, actual: packet[0] as i32 });
+    }
+    if i32::from_be_bytes(packet[1..5].try_into().unwrap()) != session_id {
+        return Err(malformed(packet));
+    }
+    Ok(&packet[5..])
+}
+
+fn malformed(packet: &[u8]) -> PingError {
+    PingError::MalformedQueryResponse(String::from_utf8_lossy(packet).to_string())
+}
+
+/// Reads a null terminated string and advances the slice past it
+fn take_cstr(payload: &mut &[u8]) -> Option<String> {
+    let end = payload.iter().position(|b| *b == 0x00)?;
+    let res = String::from_utf8_lossy(&payload[..end]).to_string();
+    *payload = &payload[end + 1..];
+    Some(res)
+}
+
+pub fn parse_handshake_response(packet: &[u8], session_id: i32) -> Result<i32, PingError> {
+    let mut payload = response_payload(packet, QUERY_HANDSHAKE, session_id)?;
+    let token = take_cstr(&mut payload).ok_or_else(|| malformed(packet))?;
+    i32::from_str(&token).map_err(|_| malformed(packet))
+}
+
+pub fn parse_basic_stat(packet: &[u8], session_id: i32) -> Result<QueryBasicStat, PingError> {
+    let mut payload = response_payload(packet, QUERY_STAT, session_id)?;
+    let mut field = || take_cstr(&mut payload).ok_or_else(|| malformed(packet));
+    let motd = field()?;
+    let gametype = field()?;
+    let map = field()?;
+    let online_players = i64::from_str(&field()?).map_err(|_| malformed(packet))?;
+    let max_players = i64::from_str(&field()?).map_err(|_| malformed(packet))?;
+    if payload.len() < 2 {
+        return Err(malformed(packet));
+    }
+    // The only little endian field of the protocol
+    let host_port = u16::from_le_bytes([payload[0], payload[1]]);
+    payload = &payload[2..];
+    let host_ip = take_cstr(&mut payload).ok_or_else(|| malformed(packet))?;
+    Ok(QueryBasicStat { motd, gametype, map, online_players, max_players, host_port, host_ip })
+}
+
+pub fn parse_full_stat(packet: &[u8], session_id: i32) -> Result<QueryFullStat, PingError> {
+    let payload = response_payload(packet, QUERY_STAT, session_id)?;
+    let mut payload = payload.strip_prefix(&FULL_STAT_KV_PADDING[..]).ok_or_else(|| malformed(packet))?;
+
+    let mut raw = Vec::new();
+    loop {
+        let key = take_cstr(&mut payload).ok_or_else(|| malformed(packet))?;
+        if key.is_empty() {
+            break;
+        }
+        let value = take_cstr(&mut payload).ok_or_else(|| malformed(packet))?;
+        raw.push((key, value));
+    }
+
+    let mut payload = payload.strip_prefix(&FULL_STAT_PLAYER_PADDING[..]).ok_or_else(|| malformed(packet))?;
+    let mut players = Vec::new();
+    loop {
+        let name = take_cstr(&mut payload).ok_or_else(|| malformed(packet))?;
+        if name.is_empty() {
+            break;
+        }
+        players.push(name);
+    }
+
+    let value = |key: &str| raw.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone()).unwrap_or_default();
+    let (server_mod, plugins) = parse_plugins(&value("plugins"));
+    Ok(QueryFullStat {
+        motd: value("hostname"),
+        gametype: value("gametype"),
+        game_id: value("game_id"),
+        version: value("version"),
+        server_mod,
+        plugins,
+        map: value("map"),
+        online_players: i64::from_str(&value("numplayers")).unwrap_or_default(),
+        max_players: i64::from_str(&value("maxplayers")).unwrap_or_default(),
+        host_port: u16::from_str(&value("hostport")).unwrap_or_default(),
+        host_ip: value("hostip"),
+        players,
+        raw,
+    })
+}
+
+/// Splits `Server Mod: plugin 1.0; other 2.0` into the server mod and plugin list
+fn parse_plugins(plugins: &str) -> (Option<String>, Vec<String>) {
+    if plugins.is_empty() {
+        return (None, Vec::new());
+    }
+    match plugins.split_once(':') {
+        Some((server_mod, list)) => {
+            let list = list.split(';').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect();
+            (Some(server_mod.trim().to_string()), list)
+        }
+        None => (Some(plugins.trim().to_string()), Vec::new()),
+    }
+}
+
+pub async fn query_basic(host: &str, port: u16, options: &PingOptions) -> Result<QueryBasicStat, PingError> {
+    let (packet, session_id) = query(host, port, options, false).await?;
+    parse_basic_stat(&packet, session_id)
+}
+
+pub async fn query_full(host: &str, port: u16, options: &PingOptions) -> Result<QueryFullStat, PingError> {
+    let (packet, session_id) = query(host, port, options, true).await?;
+    parse_full_stat(&packet, session_id)
+}
+
+async fn query(host: &str, port: u16, options: &PingOptions, full: bool) -> Result<(Vec<u8>, i32), PingError> {
+    if options.timeout == 0 {
+        return Err(PingError::InvalidTimeout);
+    }
+    let dur = options.timeout_duration();
+    try_resolved(host, port_or_default(port), options.ip_mode, |addr| async move { query_exchange(&addr, dur, full).await }).await
+}
+
+async fn query_exchange(addr: &SocketAddr, dur: Duration, full: bool) -> Result<(Vec<u8>, i32), PingError> {
+    let socket = connect_udp(addr).await?;
+
+    // Only the lower 4 bits of each byte are used by the server
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or_default();
+    let session_id = (seed as i32) & 0x0F0F0F0F;
+
+    send(&socket, dur, Phase::Handshake, &compose_query_handshake_packet(session_id)).await?;
+    let packet = recv(&socket, addr, dur, Phase::Handshake).await?;
+    let challenge_token = parse_handshake_response(&packet, session_id)?;
+
+    let request = if full {
+        compose_full_stat_packet(session_id, challenge_token)
+    } else {
+        compose_basic_stat_packet(session_id, challenge_token)
+    };
+    send(&socket, dur, Phase::Status, &request).await?;
+    let packet = recv(&socket, addr, dur, Phase::Status).await?;
+    Ok((packet, session_id))
+}
+
+async fn send(socket: &UdpSocket, dur: Duration, phase: Phase, packet: &[u8]) -> Result<(), PingError> {
+    match time::timeout(dur, socket.send(packet)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(PingError::io(phase, e)),
+        Err(_) => Err(PingError::Timeout(phase)),
+    }
+}
+
+async fn recv(socket: &UdpSocket, addr: &SocketAddr, dur: Duration, phase: Phase) -> Result<Vec<u8>, PingError> {
+    let mut buf = vec![0u8; 65535];
+    match time::timeout(dur, socket.recv(&mut buf)).await {
+        Ok(Ok(len)) => {
+            buf.truncate(len);
+            Ok(buf)
+        }
+        // ICMP port unreachable surfaces as a refused connection
+        Ok(Err(e)) if e.kind() == io::ErrorKind::ConnectionRefused => Err(PingError::ConnectionRefused(*addr)),
+        Ok(Err(e)) => Err(PingError::io(phase, e)),
+        Err(_) => Err(PingError::Timeout(phase)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION_ID: i32 = 0x01020304;
+
+    fn full_stat_response(session_id: i32) -> Vec<u8> {
+        let mut t = vec![QUERY_STAT];
+        t.extend_from_slice(&session_id.to_be_bytes());
+        t.extend_from_slice(&FULL_STAT_KV_PADDING);
+        for (k, v) in [
+            ("hostname", "A Minecraft Server"),
+            ("gametype", "SMP"),
+            ("game_id", "MINECRAFT"),
+            ("version", "1.21.8"),
+            ("plugins", "Paper on 1.21.8-R0.1-SNAPSHOT: WorldEdit 7.3.0; LuckPerms 5.4"),
+            ("map", "world"),
+            ("numplayers", "2"),
+            ("maxplayers", "20"),
+            ("hostport", "25565"),
+            ("hostip", "127.0.0.1"),
+        ] {
+            t.extend_from_slice(k.as_bytes());
+            t.push(0x00);
+            t.extend_from_slice(v.as_bytes());
+            t.push(0x00);
+        }
+        t.push(0x00);
+        t.extend_from_slice(&FULL_STAT_PLAYER_PADDING);
+        t.extend_from_slice(b"Notch\x00jeb_\x00\x00");
+        t
+    }
+
+    #[test]
+    fn test_compose_query_packets() {
+        assert_eq!(vec![0xFE, 0xFD, 0x09, 0x01, 0x02, 0x03, 0x04], compose_query_handshake_packet(SESSION_ID));
+        assert_eq!(vec![0xFE, 0xFD, 0x00, 0x01, 0x02, 0x03, 0x04, 0x00, 0x91, 0x29, 0x5B], compose_basic_stat_packet(SESSION_ID, 9513307));
+        assert_eq!(vec![0xFE, 0xFD, 0x00, 0x01, 0x02, 0x03, 0x04, 0x00, 0x91, 0x29, 0x5B, 0x00, 0x00, 0x00, 0x00], compose_full_stat_packet(SESSION_ID, 9513307));
+    }
+
+    #[test]
+    fn test_parse_handshake_response() {
+        let mut t = vec![QUERY_HANDSHAKE, 0x01, 0x02, 0x03, 0x04];
+        t.extend_from_slice(b"9513307\x00");
+        assert_eq!(9513307, parse_handshake_response(&t, SESSION_ID).unwrap());
+        let mut t = vec![QUERY_HANDSHAKE, 0x01, 0x02, 0x03, 0x04];
+        t.extend_from_slice(b"-1234\x00");
+        assert_eq!(-1234, parse_handshake_response(&t, SESSION_ID).unwrap());
+
+        assert!(parse_handshake_response(&t, 0x01020305).is_err());
+        assert!(parse_handshake_response(&t[..t.len() - 1], SESSION_ID).is_err());
+        assert!(parse_handshake_response(&t[..3], SESSION_ID).is_err());
+    }
+
+    #[test]
+    fn test_parse_basic_stat() {
+        let mut t = vec![QUERY_STAT, 0x01, 0x02, 0x03, 0x04];
+        t.extend_from_slice(b"A Minecraft Server\x00SMP\x00world\x002\x0020\x00");
+        t.extend_from_slice(&25565u16.to_le_bytes());
+        t.extend_from_slice(b"127.0.0.1\x00");
+        let res = parse_basic_stat(&t, SESSION_ID);
+        assert!(res.is_ok());
+        assert_eq!(QueryBasicStat {
+            motd: "A Minecraft Server".to_string(),
+            gametype: "SMP".to_string(),
+            map: "world".to_string(),
+            online_players: 2,
+            max_players: 20,
+            host_port: 25565,
+            host_ip: "127.0.0.1".to_string(),
+        }, res.unwrap());
+
+        assert!(parse_basic_stat(&t[..t.len() - 12], SESSION_ID).is_err());
+    }
+
+    #[test]
+    fn test_parse_full_stat() {
+        let t = full_stat_response(SESSION_ID);
+        let res = parse_full_stat(&t, SESSION_ID);
+        assert!(res.is_ok());
+        let res = res.unwrap();
+        assert_eq!("A Minecraft Server", res.motd);
+        assert_eq!("1.21.8", res.version);
+        assert_eq!(Some("Paper on 1.21.8-R0.1-SNAPSHOT".to_string()), res.server_mod);
+        assert_eq!(vec!["WorldEdit 7.3.0".to_string(), "LuckPerms 5.4".to_string()], res.plugins);
+        assert_eq!("world", res.map);
+        assert_eq!(2, res.online_players);
+        assert_eq!(20, res.max_players);
+        assert_eq!(25565, res.host_port);
+        assert_eq!(vec!["Notch".to_string(), "jeb_".to_string()], res.players);
+        assert_eq!(10, res.raw.len());
+
+        assert!(parse_full_stat(&t[..t.len() - 1], SESSION_ID).is_err());
+        assert!(parse_full_stat(&t[..20], SESSION_ID).is_err());
+    }
+
+    #[test]
+    fn test_parse_plugins() {
+        assert_eq!((None, Vec::new()), parse_plugins(""));
+        assert_eq!((Some("Vanilla".to_string()), Vec::new()), parse_plugins("Vanilla"));
+        assert_eq!((Some("CraftBukkit".to_string()), vec!["A 1".to_string()]), parse_plugins("CraftBukkit: A 1"));
+    }
+
+    #[tokio::test]
+    async fn test_query_full() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            while let Ok((len, from)) = server.recv_from(&mut buf).await {
+                let session_id = i32::from_be_bytes(buf[3..7].try_into().unwrap());
+                let res = match (buf[2], len) {
+                    (QUERY_HANDSHAKE, 7) => {
+                        let mut t = vec![QUERY_HANDSHAKE];
+                        t.extend_from_slice(&session_id.to_be_bytes());
+                        t.extend_from_slice(b"42\x00");
+                        t
+                    }
+                    (QUERY_STAT, 15) if buf[7..11] == 42i32.to_be_bytes() => full_stat_response(session_id),
+                    _ => continue,
+                };
+                let _ = server.send_to(&res, from).await;
+            }
+        });
+
+        let options = PingOptions { timeout: 2, ..Default::default() };
+        let res = query_full("127.0.0.1", port, &options).await;
+        assert!(res.is_ok());
+        assert_eq!(vec!["Notch".to_string(), "jeb_".to_string()], res.unwrap().players);
+
+        let options = PingOptions { timeout: 1, ..Default::default() };
+        assert!(matches!(query_basic("127.0.0.1", port, &options).await, Err(PingError::Timeout(Phase::Status))));
+    }
+}
@@ -0,0 +1,231 @@
+#![allow(unused)]
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time;
+
+use crate::dns::IpMode;
+use crate::error::PingError;
+use crate::protocol::{connect_host, shutoff};
+
+pub const RCON_DEFAULT_PORT: u16 = 25575;
+
+pub const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+pub const SERVERDATA_EXECCOMMAND: i32 = 2;
+pub const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+pub const SERVERDATA_AUTH: i32 = 3;
+
+/// Largest packet accepted from the server, vanilla fragments responses at 4096 bytes of body
+const MAX_RCON_PACKET_SIZE: usize = 4096 + 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RconOptions {
+    /// Timeout in seconds, applied to connecting and to each packet
+    pub timeout: u8,
+    pub ip_mode: IpMode,
+}
+
+impl RconOptions {
+    fn timeout_duration(&self) -> Duration {
+        Duration::from_secs(self.timeout as u64)
+    }
+}
+
+impl Default for RconOptions {
+    fn default() -> Self {
+        RconOptions { timeout: 5, ip_mode: IpMode::default() }
+    }
+}
+
+#[derive(Debug)]
+pub enum RconError {
+    InvalidTimeout,
+    /// Resolving the host or connecting to it failed
+    Connect(PingError),
+    Timeout,
+    Io(io::Error),
+    /// Connection closed in the middle of a packet
+    Truncated,
+    InvalidLength(i32),
+    OversizedPacket { size: usize, max: usize },
+    AuthFailed,
+    RequestIdMismatch { expected: i32, actual: i32 },
+}
+
+impl From<io::Error> for RconError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::TimedOut => RconError::Timeout,
+            io::ErrorKind::UnexpectedEof => RconError::Truncated,
+            _ => RconError::Io(e),
+        }
+    }
+}
+
+impl Display for RconError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RconError::InvalidTimeout => write!(f, "Timeout in seconds must be bigger than 0"),
+            RconError::Connect(e) => write!(f, "{e}"),
+            RconError::Timeout => write!(f, "Timed out waiting for the RCON server"),
+            RconError::Io(e) => write!(f, "I/O error during RCON: {e}"),
+            RconError::Truncated => write!(f, "RCON connection closed, response truncated"),
+            RconError::InvalidLength(len) => write!(f, "Malformed RCON packet, invalid length {len}"),
+            RconError::OversizedPacket { size, max } => write!(f, "RCON packet of {size} bytes exceeds maximum of {max} bytes"),
+            RconError::AuthFailed => write!(f, "RCON authentication failed"),
+            RconError::RequestIdMismatch { expected, actual } => write!(f, "RCON response for request {actual}, expected {expected}"),
+        }
+    }
+}
+
+impl Error for RconError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RconError::Connect(e) => Some(e),
+            RconError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+async fn rcon_io<T>(dur: Duration, fut: impl Future<Output = io::Result<T>>) -> Result<T, RconError> {
+    match time::timeout(dur, fut).await {
+        Ok(res) => res.map_err(RconError::from),
+        Err(_) => Err(RconError::Timeout),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RconPacket {
+    pub request_id: i32,
+    pub packet_type: i32,
+    pub body: String,
+}
+
+pub fn compose_rcon_packet(request_id: i32, packet_type: i32, body: &str) -> Vec<u8> {
+    // Length as little endian int. Request ID. Type. Body. Two null bytes.
+    let content_len = 4 + 4 + body.len() + 2;
+    let mut content: Vec<u8> = Vec::with_capacity(content_len + 4);
+    content.extend_from_slice(&(content_len as i32).to_le_bytes());
+    content.extend_from_slice(&request_id.to_le_bytes());
+    content.extend_from_slice(&packet_type.to_le_bytes());
+    content.extend_from_slice(body.as_bytes());
+    content.extend_from_slice(&[0x00, 0x00]);
+    content
+}
+
+pub async fn read_rcon_packet(stream: &mut TcpStream, dur: Duration) -> Result<RconPacket, RconError> {
+    let len = rcon_io(dur, stream.read_i32_le()).await?;
+    if len < 10 {
+        return Err(RconError::InvalidLength(len));
+    }
+    if len as usize > MAX_RCON_PACKET_SIZE {
+        return Err(RconError::OversizedPacket { size: len as usize, max: MAX_RCON_PACKET_SIZE });
+    }
+    let mut content = vec![0u8; len as usize];
+    rcon_io(dur, stream.read_exact(&mut content)).await?;
+    let request_id = i32::from_le_bytes(content[0..4].try_into().unwrap());
+    let packet_type = i32::from_le_bytes(content[4..8].try_into().unwrap());
+    let body = &content[8..];
+    let body = body.strip_suffix(&[0x00, 0x00]).or(body.strip_suffix(&[0x00])).unwrap_or(body);
+    let body = String::from_utf8_lossy(body).to_string();
+    Ok(RconPacket { request_id, packet_type, body })
+}
+
+/// Authenticated RCON connection
+pub struct RconClient {
+    stream: TcpStream,
+    timeout: Duration,
+    next_request_id: i32,
+}
+
+impl RconClient {
+    pub async fn connect(host: &str, port: u16, password: &str, options: &RconOptions) -> Result<RconClient, RconError> {
+        if options.timeout == 0 {
+            return Err(RconError::InvalidTimeout);
+        }
+        let port_touse = if port == 0 {
+            RCON_DEFAULT_PORT
+        } else {
+            port
+        };
+        let stream = connect_host(host, port_touse, options.ip_mode, options.timeout_duration()).await.map_err(RconError::Connect)?;
+        let mut client = RconClient { stream, timeout: options.timeout_duration(), next_request_id: 1 };
+        if let Err(e) = client.authenticate(password).await {
+            client.close().await;
+            return Err(e);
+        }
+        Ok(client)
+    }
+
+    fn request_id(&mut self) -> i32 {
+        let id = self.next_request_id;
+        self.next_request_id = self.next_request_id.wrapping_add(1).max(1);
+        id
+    }
+
+    async fn send(&mut self, request_id: i32, packet_type: i32, body: &str) -> Result<(), RconError> {
+        rcon_io(self.timeout, self.stream.write_all(&compose_rcon_packet(request_id, packet_type, body))).await
+    }
+
+    async fn authenticate(&mut self, password: &str) -> Result<(), RconError> {
+        let id = self.request_id();
+        self.send(id, SERVERDATA_AUTH, password).await?;
+        loop {
+            let packet = read_rcon_packet(&mut self.stream, self.timeout).await?;
+            // Source servers send an empty response value ahead of the auth response
+            if packet.packet_type == SERVERDATA_RESPONSE_VALUE {
+                continue;
+            }
+            if packet.request_id == -1 {
+                return Err(RconError::AuthFailed);
+            }
+            if packet.request_id != id {
+                return Err(RconError::RequestIdMismatch { expected: id, actual: packet.request_id });
+            }
+            return Ok(());
+        }
+    }
+
+    /// Runs a command and returns the reassembled response.
+    /// A second, empty packet is sent after the command, its reply marks the end of the response.
+    pub async fn command(&mut self, command: &str) -> Result<String, RconError> {
+        let id = self.request_id();
+        let terminator_id = self.request_id();
+        self.send(id, SERVERDATA_EXECCOMMAND, command).await?;
+        self.send(terminator_id, SERVERDATA_RESPONSE_VALUE, "").await?;
+
+        let mut res = String::new();
+        loop {
+            let packet = read_rcon_packet(&mut self.stream, self.timeout).await?;
+            if packet.request_id == terminator_id {
+                return Ok(res);
+            }
+            if packet.request_id != id {
+                return Err(RconError::RequestIdMismatch { expected: id, actual: packet.request_id });
+            }
+            res.push_str(&packet.body);
+        }
+    }
+
+    pub async fn close(mut self) {
+        shutoff(&mut self.stream).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_rcon_packet() {
+        assert_eq!(vec![10, 0, 0, 0, 1, 0, 0, 0, 3, 0, 0, 0, 0, 0], compose_rcon_packet(1, SERVERDATA_AUTH, ""));
+        assert_eq!(vec![14, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 2, 0, 0, 0, 0x6C, 0x69, 0x73, 0x74, 0, 0], compose_rcon_packet(-1, SERVERDATA_EXECCOMMAND, "list"));
+    }
+}
@@ -0,0 +1,159 @@
+#![allow(unused)]
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::error::{Phase, PingError};
+use crate::packet::{compose_login_disconnect_packet, compose_pong_response_packet, compose_status_response_packet, Intent, parse_handshake_packet, read_frame};
+use crate::protocol::{LATEST, shutoff, with_timeout};
+use crate::status::{StatusPlayers, StatusResponse, StatusVersion};
+
+/// Largest packet accepted from a client, a handshake with a 255 character host fits well within
+pub(crate) const MAX_SERVERBOUND_FRAME_SIZE: usize = 2048;
+
+#[derive(Clone, Debug)]
+pub struct ServerOptions {
+    /// Sent as the Status Response, the description doubles as the login disconnect reason
+    pub status: StatusResponse,
+    /// Answer with the protocol number of the client's handshake so every client version shows as compatible
+    pub echo_protocol: bool,
+    /// Timeout in seconds for each read from a client
+    pub timeout: u8,
+}
+
+impl ServerOptions {
+    fn timeout_duration(&self) -> Duration {
+        Duration::from_secs(self.timeout as u64)
+    }
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        ServerOptions {
+            status: StatusResponse {
+                version: Some(StatusVersion { name: "RollingGlass".to_string(), protocol: LATEST }),
+                players: Some(StatusPlayers { max: 20, online: 0, sample: Vec::new() }),
+                description: Some(Value::String("A Minecraft Server".to_string())),
+                ..Default::default()
+            },
+            echo_protocol: false,
+            timeout: 5,
+        }
+    }
+}
+
+/// Favicon field value for a 64x64 PNG image
+pub fn favicon_from_png(png: &[u8]) -> String {
+    format!("data:image/png;base64,{}", STANDARD.encode(png))
+}
+
+/// Java Edition listener answering server list pings with a fixed status
+pub struct StatusServer {
+    listener: TcpListener,
+    options: Arc<ServerOptions>,
+}
+
+impl StatusServer {
+    pub async fn bind(addr: SocketAddr, options: ServerOptions) -> io::Result<StatusServer> {
+        let listener = TcpListener::bind(addr).await?;
+        Ok(StatusServer { listener, options: Arc::new(options) })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections until the listener fails, each client is handled on its own task
+    pub async fn serve(self) -> io::Result<()> {
+        loop {
+            let (mut stream, _) = self.listener.accept().await?;
+            let options = self.options.clone();
+            tokio::spawn(async move {
+                let _ = handle_client(&mut stream, &options).await;
+                shutoff(&mut stream).await;
+            });
+        }
+    }
+}
+
+async fn handle_client(stream: &mut TcpStream, options: &ServerOptions) -> Result<(), PingError> {
+    let dur = options.timeout_duration();
+    let handshake = parse_handshake_packet(&with_timeout(dur, Phase::Handshake, read_frame(stream, Phase::Handshake, MAX_SERVERBOUND_FRAME_SIZE)).await?)?;
+
+    if handshake.next_state != Intent::Status {
+        let reason = options.status.description.clone().unwrap_or(Value::String(String::new()));
+        let packet = compose_login_disconnect_packet(&reason.to_string());
+        return with_timeout(dur, Phase::Handshake, stream.write_all(&packet)).await;
+    }
+
+    loop {
+        let content = with_timeout(dur, Phase::Status, read_frame(stream, Phase::Status, MAX_SERVERBOUND_FRAME_SIZE)).await?;
+        match content.first() {
+            Some(0x00) => {
+                let mut status = options.status.clone();
+                if options.echo_protocol {
+                    let version = status.version.get_or_insert_with(Default::default);
+                    version.protocol = handshake.protocol;
+                }
+                let json = serde_json::to_string(&status).map_err(PingError::InvalidJson)?;
+                with_timeout(dur, Phase::Status, stream.write_all(&compose_status_response_packet(&json))).await?;
+            }
+            Some(0x01) if content.len() == 9 => {
+                let payload = i64::from_be_bytes(content[1..9].try_into().unwrap());
+                with_timeout(dur, Phase::Ping, stream.write_all(&compose_pong_response_packet(payload))).await?;
+                return Ok(());
+            }
+            Some(id) => return Err(PingError::UnexpectedPacketId { expected: 0x00, actual: *id as i32 }),
+            None => return Err(PingError::InvalidLength(0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::{MINECRAFT_1_12_2, ping_with_options, PingOptions};
+
+    use super::*;
+
+    async fn spawn_server(options: ServerOptions) -> u16 {
+        let server = StatusServer::bind("127.0.0.1:0".parse().unwrap(), options).await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(server.serve());
+        port
+    }
+
+    #[test]
+    fn test_favicon_from_png() {
+        assert_eq!("data:image/png;base64,iVBORw==", favicon_from_png(&[0x89, 0x50, 0x4E, 0x47]));
+    }
+
+    #[tokio::test]
+    async fn test_status_server() {
+        let mut options = ServerOptions::default();
+        options.status.description = Some(Value::String("Down for maintenance".to_string()));
+        options.status.favicon = Some(favicon_from_png(&[0x89, 0x50, 0x4E, 0x47]));
+        let port = spawn_server(options).await;
+
+        let ping_options = PingOptions { timeout: 2, ..Default::default() };
+        let res = ping_with_options("127.0.0.1", port, &ping_options).await;
+        assert!(res.is_ok());
+        let res = res.unwrap().response;
+        assert_eq!("Down for maintenance", res.description_text());
+        assert_eq!(Some(StatusVersion { name: "RollingGlass".to_string(), protocol: LATEST }), res.version);
+        assert_eq!(Some("data:image/png;base64,iVBORw==".to_string()), res.favicon);
+
+        let port = spawn_server(ServerOptions { echo_protocol: true, ..Default::default() }).await;
+        let ping_options = PingOptions { protocol: MINECRAFT_1_12_2, timeout: 2, ..Default::default() };
+        let res = ping_with_options("127.0.0.1", port, &ping_options).await;
+        assert!(res.is_ok());
+        assert_eq!(MINECRAFT_1_12_2, res.unwrap().response.version.unwrap().protocol);
+    }
+}
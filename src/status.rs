@@ -0,0 +1,179 @@
+#![allow(unused)]
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::PingError;
+use crate::legacy::LegacyVariant;
+use crate::protocol::ProtocolNum;
+use crate::version::VersionRegistry;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusVersion {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub protocol: ProtocolNum,
+}
+
+impl StatusVersion {
+    /// Release names of the protocol number, `1.21.7–1.21.8`
+    pub fn release_names(&self) -> Option<String> {
+        VersionRegistry::current().display_name(self.protocol)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusPlayerSample {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub id: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusPlayers {
+    #[serde(default)]
+    pub max: i64,
+    #[serde(default)]
+    pub online: i64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sample: Vec<StatusPlayerSample>,
+}
+
+/// Status Response JSON as sent by the server.
+/// Fields not modelled here are kept in `extra`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<StatusVersion>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub players: Option<StatusPlayers>,
+    /// Chat component, either a plain string or an object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+    #[serde(rename = "enforcesSecureChat", default, skip_serializing_if = "Option::is_none")]
+    pub enforces_secure_chat: Option<bool>,
+    #[serde(rename = "previewsChat", default, skip_serializing_if = "Option::is_none")]
+    pub previews_chat: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modinfo: Option<Value>,
+    #[serde(rename = "forgeData", default, skip_serializing_if = "Option::is_none")]
+    pub forge_data: Option<Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl StatusResponse {
+    pub fn from_slice(raw: &[u8]) -> Result<StatusResponse, PingError> {
+        serde_json::from_slice(raw).map_err(PingError::InvalidJson)
+    }
+
+    /// Description flattened to plain text, formatting dropped
+    pub fn description_text(&self) -> String {
+        let mut res = String::new();
+        if let Some(description) = &self.description {
+            flatten_chat_component(description, &mut res);
+        }
+        res
+    }
+}
+
+/// Parsed status together with the bytes it was parsed from
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatusResult {
+    pub response: StatusResponse,
+    pub raw: Vec<u8>,
+    /// Round trip of the Ping Request / Pong Response exchange, or of the whole request for legacy pings.
+    /// `None` when the server answered the status but not the Ping Request.
+    pub latency: Option<Duration>,
+    /// Set when the status was obtained with a legacy ping
+    pub legacy: Option<LegacyVariant>,
+    /// Protocol number sent in the handshake, `None` for legacy pings
+    pub requested_protocol: Option<ProtocolNum>,
+    /// Protocol number of the version announced by the server
+    pub advertised_protocol: Option<ProtocolNum>,
+}
+
+/// Chat component flattened to plain text, formatting dropped
+pub(crate) fn chat_text(component: &Value) -> String {
+    let mut res = String::new();
+    flatten_chat_component(component, &mut res);
+    res
+}
+
+fn flatten_chat_component(component: &Value, out: &mut String) {
+    match component {
+        Value::String(s) => out.push_str(s),
+        Value::Array(arr) => arr.iter().for_each(|v| flatten_chat_component(v, out)),
+        Value::Object(obj) => {
+            if let Some(Value::String(text)) = obj.get("text") {
+                out.push_str(text);
+            }
+            if let Some(extra) = obj.get("extra") {
+                flatten_chat_component(extra, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status_response() {
+        let raw = br#"{
+            "version": {"name": "1.21.8", "protocol": 772},
+            "players": {"max": 100, "online": 5, "sample": [{"name": "thinkofdeath", "id": "4566e69f-c907-48ee-8d71-d7ba5aa00d20"}]},
+            "description": {"text": "Hello ", "extra": [{"text": "world", "bold": true}]},
+            "favicon": "data:image/png;base64,AAAA",
+            "enforcesSecureChat": true,
+            "previewsChat": false,
+            "preventsChatReports": true
+        }"#;
+        let res = StatusResponse::from_slice(raw);
+        assert!(res.is_ok());
+        let res = res.unwrap();
+        let version = res.version.as_ref().unwrap();
+        assert_eq!("1.21.8", version.name);
+        assert_eq!(772, version.protocol);
+        assert_eq!(Some("1.21.7\u{2013}1.21.8".to_string()), version.release_names());
+        let players = res.players.as_ref().unwrap();
+        assert_eq!(100, players.max);
+        assert_eq!(5, players.online);
+        assert_eq!(1, players.sample.len());
+        assert_eq!("thinkofdeath", players.sample[0].name);
+        assert_eq!("Hello world", res.description_text());
+        assert_eq!(Some("data:image/png;base64,AAAA".to_string()), res.favicon);
+        assert_eq!(Some(true), res.enforces_secure_chat);
+        assert_eq!(Some(false), res.previews_chat);
+        assert!(res.modinfo.is_none());
+        assert!(res.forge_data.is_none());
+        assert_eq!(Some(&Value::Bool(true)), res.extra.get("preventsChatReports"));
+    }
+
+    #[test]
+    fn test_parse_minimal_status_response() {
+        let res = StatusResponse::from_slice(br#"{"description": "A Minecraft Server"}"#);
+        assert!(res.is_ok());
+        let res = res.unwrap();
+        assert!(res.version.is_none());
+        assert!(res.players.is_none());
+        assert_eq!("A Minecraft Server", res.description_text());
+        assert!(res.extra.is_empty());
+
+        let res = StatusResponse::from_slice(br#"{"modinfo": {"type": "FML", "modList": []}}"#);
+        assert!(res.is_ok());
+        assert!(res.unwrap().modinfo.is_some());
+
+        assert!(StatusResponse::from_slice(b"").is_err());
+        assert!(StatusResponse::from_slice(b"{\"version\": ").is_err());
+        assert!(StatusResponse::from_slice(b"[]").is_err());
+    }
+}
@@ -0,0 +1,558 @@
+#![allow(unused)]
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::slice;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, copy_bidirectional};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::error::{Phase, PingError};
+use crate::haproxy::{ProxyHeader, read_proxy_header};
+use crate::packet::*;
+use crate::protocol::{LATEST, ProtocolNum, shutoff, with_timeout};
+use crate::proxy::ProxyKind;
+use crate::server::MAX_SERVERBOUND_FRAME_SIZE;
+
+#[derive(Clone, Debug, Default)]
+struct StubRecords {
+    /// Names answered by the stub, with or without records
+    names: HashSet<String>,
+    a: HashMap<String, Vec<Ipv4Addr>>,
+    aaaa: HashMap<String, Vec<Ipv6Addr>>,
+    srv: HashMap<String, Vec<(String, u16)>>,
+}
+
+/// Records of the stub resolvers currently installed, by install id
+fn stub_records() -> &'static Mutex<Vec<(u64, StubRecords)>> {
+    static RECORDS: OnceLock<Mutex<Vec<(u64, StubRecords)>>> = OnceLock::new();
+    RECORDS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+fn normalize(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Records of every installed stub for the name, `None` when no installed stub knows the name
+fn stub_lookup<T: Clone>(host: &str, f: impl Fn(&StubRecords) -> &HashMap<String, Vec<T>>) -> Option<Vec<T>> {
+    let host = normalize(host);
+    let records = stub_records().lock().unwrap();
+    let covering: Vec<&StubRecords> = records.iter().map(|(_, r)| r).filter(|r| r.names.contains(&host)).collect();
+    if covering.is_empty() {
+        return None;
+    }
+    Some(covering.into_iter().filter_map(|r| f(r).get(&host)).flatten().cloned().collect())
+}
+
+pub(crate) fn stub_ipv4(host: &str) -> Option<Vec<Ipv4Addr>> {
+    stub_lookup(host, |r| &r.a)
+}
+
+pub(crate) fn stub_ipv6(host: &str) -> Option<Vec<Ipv6Addr>> {
+    stub_lookup(host, |r| &r.aaaa)
+}
+
+pub(crate) fn stub_srv(host: &str) -> Option<Vec<(String, u16)>> {
+    stub_lookup(host, |r| &r.srv)
+}
+
+/// In-memory DNS records answering lookups of the names they hold once installed.
+/// A stubbed name has no records but those added, other names go to the system resolver.
+#[derive(Clone, Debug, Default)]
+pub struct StubResolver {
+    records: StubRecords,
+}
+
+impl StubResolver {
+    pub fn new() -> StubResolver {
+        StubResolver::default()
+    }
+
+    /// Adds an A or AAAA record depending on the address family
+    pub fn ip(mut self, host: &str, ip: IpAddr) -> StubResolver {
+        self.records.names.insert(normalize(host));
+        match ip {
+            IpAddr::V4(v) => self.records.a.entry(normalize(host)).or_default().push(v),
+            IpAddr::V6(v) => self.records.aaaa.entry(normalize(host)).or_default().push(v),
+        }
+        self
+    }
+
+    /// Adds a `_minecraft._tcp` SRV record for `host`
+    pub fn srv(mut self, host: &str, target: &str, port: u16) -> StubResolver {
+        self.records.names.insert(normalize(host));
+        self.records.srv.entry(normalize(host)).or_default().push((target.to_string(), port));
+        self
+    }
+
+    /// Adds a name without any record, failing to resolve instead of reaching the system resolver
+    pub fn unresolvable(mut self, host: &str) -> StubResolver {
+        self.records.names.insert(normalize(host));
+        self
+    }
+
+    /// Installs the records next to those of other installed stubs, tests running in parallel can each install their own hosts.
+    /// The records are removed when the returned guard is dropped.
+    pub fn install(self) -> StubGuard {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        stub_records().lock().unwrap().push((id, self.records));
+        StubGuard { id }
+    }
+}
+
+/// Keeps the records of a [`StubResolver`] installed, its names go back to the system resolver once dropped
+#[must_use = "the stub resolver is uninstalled when the guard is dropped"]
+#[derive(Debug)]
+pub struct StubGuard {
+    id: u64,
+}
+
+impl Drop for StubGuard {
+    fn drop(&mut self) {
+        if let Ok(mut records) = stub_records().lock() {
+            records.retain(|(id, _)| *id != self.id);
+        }
+    }
+}
+
+/// What the mock server answers to a Ping Request
+#[derive(Clone, Debug, PartialEq)]
+pub enum MockPong {
+    Echo,
+    /// Pong with this payload instead of the one received
+    Payload(i64),
+    /// Never answer
+    Silent,
+    /// Close the connection instead of answering
+    Close,
+}
+
+/// Misbehaviour of the mock server when answering the Status Request
+#[derive(Clone, Debug, PartialEq)]
+pub enum MockFault {
+    /// Close the connection right after reading the handshake
+    CloseAfterHandshake,
+    /// Send half of the Status Response then stop sending, keeping the connection open
+    StallMidFrame,
+    /// Send half of the Status Response then close, the announced length is never reached
+    TruncateAndClose,
+    /// Announce the packet length with a 6 byte varint
+    OverlongVarInt,
+    /// Send the Status Response one byte at a time, waiting this long before each byte
+    Drip(Duration),
+    /// Send the Status Response with this packet ID instead of 0x00
+    PacketId(u8),
+}
+
+/// What the mock server answers to Login Start from clients of an accepted protocol
+#[derive(Clone, Debug, PartialEq)]
+pub enum MockLogin {
+    /// Encryption Request with a 4 byte verify token, like an online mode server
+    Encrypt,
+    /// Login Success with the UUID and name of Login Start, like an offline mode server
+    Success,
+    /// Set Compression with this threshold, then an uncompressed Login Success
+    Compress(i32),
+    /// Login Disconnect with this JSON chat component
+    Disconnect(String),
+}
+
+/// Answers of the mock server to status clients
+#[derive(Clone, Debug)]
+pub struct MockStatusScript {
+    /// Status Response JSON, sent verbatim so it may be invalid on purpose
+    pub json: String,
+    pub pong: MockPong,
+    pub fault: Option<MockFault>,
+}
+
+impl Default for MockStatusScript {
+    fn default() -> Self {
+        MockStatusScript {
+            json: r#"{"version":{"name":"Mock","protocol":47},"players":{"max":20,"online":0},"description":"A mock server"}"#.to_string(),
+            pong: MockPong::Echo,
+            fault: None,
+        }
+    }
+}
+
+/// Answers of the mock server to clients logging in
+#[derive(Clone, Debug)]
+pub struct MockLoginScript {
+    pub reply: MockLogin,
+    /// Protocols allowed to log in, older clients are told they are outdated and newer ones that the server is.
+    /// All are allowed when `None`.
+    pub protocols: Option<RangeInclusive<ProtocolNum>>,
+    /// Let players in with the Transfer intent, refused right after the handshake otherwise like vanilla
+    pub accepts_transfers: bool,
+    /// Refuse logins without BungeeCord forwarded player data, like a backend with `bungeecord: true`
+    pub bungee_forwarding: bool,
+}
+
+impl Default for MockLoginScript {
+    fn default() -> Self {
+        MockLoginScript { reply: MockLogin::Encrypt, protocols: None, accepts_transfers: false, bungee_forwarding: false }
+    }
+}
+
+/// Behaviour of the mock server, the connection settings and one script per handshake intent
+#[derive(Clone, Debug, Default)]
+pub struct MockScript {
+    /// Close the connection unless the handshake is addressed to this host, like a proxy with forced hosts
+    pub virtual_host: Option<String>,
+    /// Expect a PROXY protocol header before the handshake, closing connections without one
+    pub proxy_protocol: bool,
+    pub status: MockStatusScript,
+    pub login: MockLoginScript,
+}
+
+impl From<MockStatusScript> for MockScript {
+    fn from(status: MockStatusScript) -> Self {
+        MockScript { status, ..Default::default() }
+    }
+}
+
+impl From<MockLoginScript> for MockScript {
+    fn from(login: MockLoginScript) -> Self {
+        MockScript { login, ..Default::default() }
+    }
+}
+
+/// In-process Java Edition server answering handshakes, status and ping requests from a script
+pub struct MockServer {
+    addr: SocketAddr,
+    received: Arc<Received>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct Received {
+    proxy_headers: Mutex<Vec<ProxyHeader>>,
+    handshakes: Mutex<Vec<Handshake>>,
+    logins: Mutex<Vec<LoginStart>>,
+}
+
+impl MockServer {
+    /// Listens on a random port of 127.0.0.1
+    pub async fn start(script: impl Into<MockScript>) -> MockServer {
+        MockServer::start_on(IpAddr::from([127, 0, 0, 1]), script).await
+    }
+
+    /// Listens on a random port of the given address, such as `::1` for IPv6 tests
+    pub async fn start_on(ip: IpAddr, script: impl Into<MockScript>) -> MockServer {
+        let listener = TcpListener::bind(SocketAddr::new(ip, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let received = Arc::new(Received::default());
+        let script = Arc::new(script.into());
+        let task_received = received.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let script = script.clone();
+                let received = task_received.clone();
+                tokio::spawn(async move {
+                    let _ = handle_mock_client(&mut stream, &script, &received).await;
+                    shutoff(&mut stream).await;
+                });
+            }
+        });
+        MockServer { addr, received, task }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// PROXY protocol headers received so far, in order
+    pub fn proxy_headers(&self) -> Vec<ProxyHeader> {
+        self.received.proxy_headers.lock().unwrap().clone()
+    }
+
+    /// Handshakes received so far, in order
+    pub fn handshakes(&self) -> Vec<Handshake> {
+        self.received.handshakes.lock().unwrap().clone()
+    }
+
+    /// Login Start packets received so far, in order
+    pub fn logins(&self) -> Vec<LoginStart> {
+        self.received.logins.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// In-process SOCKS5 or HTTP CONNECT proxy tunnelling to any address, resolving names itself
+pub struct MockProxy {
+    addr: SocketAddr,
+    targets: Arc<Mutex<Vec<String>>>,
+    task: JoinHandle<()>,
+}
+
+impl MockProxy {
+    /// Listens on a random port of 127.0.0.1, requiring the username and password if given
+    pub async fn start(kind: ProxyKind, credentials: Option<(String, String)>) -> MockProxy {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let targets = Arc::new(Mutex::new(Vec::new()));
+        let credentials = Arc::new(credentials);
+        let task_targets = targets.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let credentials = credentials.clone();
+                let targets = task_targets.clone();
+                tokio::spawn(async move {
+                    let tunnel = match kind {
+                        ProxyKind::Socks5 => accept_mock_socks5(&mut stream, &credentials, &targets).await,
+                        ProxyKind::HttpConnect => accept_mock_http_connect(&mut stream, &credentials, &targets).await,
+                    };
+                    if let Ok(Some(mut upstream)) = tunnel {
+                        let _ = copy_bidirectional(&mut stream, &mut upstream).await;
+                    }
+                    shutoff(&mut stream).await;
+                });
+            }
+        });
+        MockProxy { addr, targets, task }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// `host:port` of the tunnels opened so far, in order, with names as the client sent them
+    pub fn targets(&self) -> Vec<String> {
+        self.targets.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn read_mock_bytes(stream: &mut TcpStream, len: usize) -> io::Result<Vec<u8>> {
+    let mut res = vec![0u8; len];
+    stream.read_exact(&mut res).await?;
+    Ok(res)
+}
+
+async fn accept_mock_socks5(stream: &mut TcpStream, credentials: &Option<(String, String)>, targets: &Mutex<Vec<String>>) -> io::Result<Option<TcpStream>> {
+    let greeting = read_mock_bytes(stream, 2).await?;
+    let methods = read_mock_bytes(stream, greeting[1] as usize).await?;
+    let Some((username, password)) = credentials else {
+        stream.write_all(&[0x05, 0x00]).await?;
+        return connect_mock_socks5(stream, targets).await;
+    };
+    if !methods.contains(&0x02) {
+        stream.write_all(&[0x05, 0xFF]).await?;
+        return Ok(None);
+    }
+    stream.write_all(&[0x05, 0x02]).await?;
+    let len = read_mock_bytes(stream, 2).await?[1] as usize;
+    let sent_username = read_mock_bytes(stream, len).await?;
+    let len = read_mock_bytes(stream, 1).await?[0] as usize;
+    let sent_password = read_mock_bytes(stream, len).await?;
+    if sent_username != username.as_bytes() || sent_password != password.as_bytes() {
+        stream.write_all(&[0x01, 0x01]).await?;
+        return Ok(None);
+    }
+    stream.write_all(&[0x01, 0x00]).await?;
+    connect_mock_socks5(stream, targets).await
+}
+
+async fn connect_mock_socks5(stream: &mut TcpStream, targets: &Mutex<Vec<String>>) -> io::Result<Option<TcpStream>> {
+    let request = read_mock_bytes(stream, 4).await?;
+    let host = match request[3] {
+        0x01 => Ipv4Addr::from(<[u8; 4]>::try_from(read_mock_bytes(stream, 4).await?).unwrap()).to_string(),
+        0x04 => format!("[{}]", Ipv6Addr::from(<[u8; 16]>::try_from(read_mock_bytes(stream, 16).await?).unwrap())),
+        _ => {
+            let len = read_mock_bytes(stream, 1).await?[0] as usize;
+            String::from_utf8_lossy(&read_mock_bytes(stream, len).await?).to_string()
+        }
+    };
+    let port = read_mock_bytes(stream, 2).await?;
+    let target = format!("{host}:{}", u16::from_be_bytes([port[0], port[1]]));
+    targets.lock().unwrap().push(target.clone());
+    match TcpStream::connect(&target).await {
+        Ok(upstream) => {
+            stream.write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]).await?;
+            Ok(Some(upstream))
+        }
+        Err(_) => {
+            stream.write_all(&[0x05, 0x05, 0x00, 0x01, 0, 0, 0, 0, 0, 0]).await?;
+            Ok(None)
+        }
+    }
+}
+
+async fn accept_mock_http_connect(stream: &mut TcpStream, credentials: &Option<(String, String)>, targets: &Mutex<Vec<String>>) -> io::Result<Option<TcpStream>> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await?);
+    }
+    let head = String::from_utf8_lossy(&head).to_string();
+    let target = head.split(' ').nth(1).unwrap_or_default().to_string();
+    if let Some((username, password)) = credentials {
+        let expected = format!("Proxy-Authorization: Basic {}", STANDARD.encode(format!("{username}:{password}")));
+        if !head.lines().any(|v| v == expected) {
+            stream.write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n").await?;
+            return Ok(None);
+        }
+    }
+    targets.lock().unwrap().push(target.clone());
+    match TcpStream::connect(&target).await {
+        Ok(upstream) => {
+            stream.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").await?;
+            Ok(Some(upstream))
+        }
+        Err(_) => {
+            stream.write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n").await?;
+            Ok(None)
+        }
+    }
+}
+
+async fn handle_mock_client(stream: &mut TcpStream, script: &MockScript, received: &Received) -> Result<(), PingError> {
+    let dur = Duration::from_secs(5);
+    if script.proxy_protocol {
+        let header = with_timeout(dur, Phase::Handshake, read_proxy_header(stream)).await?;
+        received.proxy_headers.lock().unwrap().push(header);
+    }
+    let handshake = parse_handshake_packet(&with_timeout(dur, Phase::Handshake, read_frame(stream, Phase::Handshake, MAX_SERVERBOUND_FRAME_SIZE)).await?)?;
+    received.handshakes.lock().unwrap().push(handshake.clone());
+    if script.virtual_host.as_ref().is_some_and(|v| *v != handshake.host) {
+        return Ok(());
+    }
+    match handshake.next_state {
+        Intent::Status => handle_mock_status(stream, &script.status).await,
+        _ => handle_mock_login(stream, &script.login, received, &handshake).await,
+    }
+}
+
+async fn handle_mock_status(stream: &mut TcpStream, script: &MockStatusScript) -> Result<(), PingError> {
+    let dur = Duration::from_secs(5);
+    if script.fault == Some(MockFault::CloseAfterHandshake) {
+        return Ok(());
+    }
+
+    loop {
+        let content = with_timeout(dur, Phase::Status, read_frame(stream, Phase::Status, MAX_SERVERBOUND_FRAME_SIZE)).await?;
+        match content.first() {
+            Some(0x00) => {
+                let packet = compose_status_response_packet(&script.json);
+                match &script.fault {
+                    None | Some(MockFault::CloseAfterHandshake) => {
+                        with_timeout(dur, Phase::Status, stream.write_all(&packet)).await?;
+                    }
+                    Some(MockFault::StallMidFrame) => {
+                        with_timeout(dur, Phase::Status, stream.write_all(&packet[..packet.len() / 2])).await?;
+                        std::future::pending::<()>().await;
+                    }
+                    Some(MockFault::TruncateAndClose) => {
+                        return with_timeout(dur, Phase::Status, stream.write_all(&packet[..packet.len() / 2])).await;
+                    }
+                    Some(MockFault::OverlongVarInt) => {
+                        with_timeout(dur, Phase::Status, stream.write_all(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x80, 0x00])).await?;
+                        std::future::pending::<()>().await;
+                    }
+                    Some(MockFault::Drip(interval)) => {
+                        for byte in packet.iter() {
+                            tokio::time::sleep(*interval).await;
+                            with_timeout(dur, Phase::Status, stream.write_all(slice::from_ref(byte))).await?;
+                        }
+                    }
+                    Some(MockFault::PacketId(id)) => {
+                        // The packet ID directly follows the length, a one or two byte varint for small statuses
+                        let mut packet = packet;
+                        let id_pos = packet.iter().position(|b| b & 0x80 == 0).unwrap() + 1;
+                        packet[id_pos] = *id;
+                        with_timeout(dur, Phase::Status, stream.write_all(&packet)).await?;
+                    }
+                }
+            }
+            Some(0x01) if content.len() == 9 => {
+                let payload = match script.pong {
+                    MockPong::Echo => i64::from_be_bytes(content[1..9].try_into().unwrap()),
+                    MockPong::Payload(v) => v,
+                    MockPong::Silent => std::future::pending().await,
+                    MockPong::Close => return Ok(()),
+                };
+                return with_timeout(dur, Phase::Ping, stream.write_all(&compose_pong_response_packet(payload))).await;
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+async fn handle_mock_login(stream: &mut TcpStream, script: &MockLoginScript, received: &Received, handshake: &Handshake) -> Result<(), PingError> {
+    let dur = Duration::from_secs(5);
+    if handshake.next_state == Intent::Transfer && !script.accepts_transfers {
+        let packet = compose_login_disconnect_packet(r#"{"translate":"multiplayer.disconnect.transfers_disabled"}"#);
+        return with_timeout(dur, Phase::Login, stream.write_all(&packet)).await;
+    }
+    if script.bungee_forwarding && BungeeForwarding::parse(&handshake.host).is_none() {
+        let packet = compose_login_disconnect_packet(r#"{"text":"If you wish to use IP forwarding, please enable it in your BungeeCord config as well!"}"#);
+        return with_timeout(dur, Phase::Login, stream.write_all(&packet)).await;
+    }
+
+    let protocol = handshake.protocol;
+    let content = with_timeout(dur, Phase::Login, read_frame(stream, Phase::Login, MAX_SERVERBOUND_FRAME_SIZE)).await?;
+    let start: LoginStart = decode_versioned_packet(protocol, &content)?;
+    received.logins.lock().unwrap().push(start.clone());
+
+    let accepted = script.protocols.as_ref().is_none_or(|v| v.contains(&protocol));
+    let packet = match &script.reply {
+        _ if script.protocols.as_ref().is_some_and(|v| protocol < *v.start()) => {
+            compose_login_disconnect_packet(r#"{"text":"Outdated client! Please use 1.21.8"}"#)
+        }
+        _ if !accepted => {
+            compose_login_disconnect_packet(r#"{"translate":"multiplayer.disconnect.outdated_server","with":["1.21.8"]}"#)
+        }
+        MockLogin::Encrypt => encode_versioned_packet(protocol, &EncryptionRequest {
+            server_id: String::new(),
+            public_key: vec![0x30; 162],
+            verify_token: vec![0x01, 0x02, 0x03, 0x04],
+            should_authenticate: true,
+        }),
+        MockLogin::Success => encode_versioned_packet(protocol, &LoginSuccess {
+            uuid: start.uuid.unwrap_or_default(),
+            name: start.name,
+            properties: Vec::new(),
+        }),
+        MockLogin::Compress(threshold) => {
+            with_timeout(dur, Phase::Login, stream.write_all(&encode_packet(&SetCompression { threshold: *threshold }))).await?;
+            // Packets below the threshold are sent uncompressed, after a data length of 0
+            let success = LoginSuccess { uuid: start.uuid.unwrap_or_default(), name: start.name, properties: Vec::new() };
+            let mut content = vec![0x00];
+            write_varint(&mut content, LoginSuccess::ID);
+            success.encode_for(protocol, &mut content);
+            let mut packet = Vec::new();
+            write_varint(&mut packet, content.len() as i32);
+            packet.append(&mut content);
+            packet
+        }
+        MockLogin::Disconnect(reason) => compose_login_disconnect_packet(reason),
+    };
+    with_timeout(dur, Phase::Login, stream.write_all(&packet)).await
+}
@@ -0,0 +1,468 @@
+#![allow(unused)]
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::protocol::{
+    is_snapshot_protocol_number, MINECRAFT_1_10, MINECRAFT_1_10_1, MINECRAFT_1_10_2, MINECRAFT_1_11, MINECRAFT_1_11_1,
+    MINECRAFT_1_11_2, MINECRAFT_1_12, MINECRAFT_1_12_1, MINECRAFT_1_12_2, MINECRAFT_1_13, MINECRAFT_1_13_1,
+    MINECRAFT_1_13_2, MINECRAFT_1_14, MINECRAFT_1_14_1, MINECRAFT_1_14_2, MINECRAFT_1_14_3, MINECRAFT_1_14_4,
+    MINECRAFT_1_15, MINECRAFT_1_15_1, MINECRAFT_1_15_2, MINECRAFT_1_16, MINECRAFT_1_16_1, MINECRAFT_1_16_2,
+    MINECRAFT_1_16_3, MINECRAFT_1_16_4, MINECRAFT_1_16_5, MINECRAFT_1_17, MINECRAFT_1_17_1, MINECRAFT_1_18,
+    MINECRAFT_1_18_1, MINECRAFT_1_18_2, MINECRAFT_1_19, MINECRAFT_1_19_1, MINECRAFT_1_19_2, MINECRAFT_1_19_3,
+    MINECRAFT_1_19_4, MINECRAFT_1_20, MINECRAFT_1_20_1, MINECRAFT_1_20_2, MINECRAFT_1_20_3, MINECRAFT_1_20_4,
+    MINECRAFT_1_20_5, MINECRAFT_1_20_6, MINECRAFT_1_21, MINECRAFT_1_21_1, MINECRAFT_1_21_2, MINECRAFT_1_21_3,
+    MINECRAFT_1_21_4, MINECRAFT_1_21_5, MINECRAFT_1_21_6, MINECRAFT_1_21_7, MINECRAFT_1_21_8, MINECRAFT_1_7,
+    MINECRAFT_1_7_1, MINECRAFT_1_7_10, MINECRAFT_1_7_2, MINECRAFT_1_7_3, MINECRAFT_1_7_4, MINECRAFT_1_7_5,
+    MINECRAFT_1_7_6, MINECRAFT_1_7_7, MINECRAFT_1_7_8, MINECRAFT_1_7_9, MINECRAFT_1_8, MINECRAFT_1_8_1, MINECRAFT_1_8_2,
+    MINECRAFT_1_8_3, MINECRAFT_1_8_4, MINECRAFT_1_8_5, MINECRAFT_1_8_6, MINECRAFT_1_8_7, MINECRAFT_1_8_8,
+    MINECRAFT_1_8_9, MINECRAFT_1_9, MINECRAFT_1_9_1, MINECRAFT_1_9_2, MINECRAFT_1_9_3, MINECRAFT_1_9_4, ProtocolNum,
+    snapshot_protocol, SNAPSHOT_PROTOCOL_BIT,
+};
+
+/// Release names, protocol numbers and release dates, oldest first.
+/// 1.7, 1.7.1 and 1.7.3 only shipped as pre-releases.
+const BUILTIN_VERSIONS: &[(&str, ProtocolNum, Option<&str>)] = &[
+    ("1.7", MINECRAFT_1_7, None),
+    ("1.7.1", MINECRAFT_1_7_1, None),
+    ("1.7.2", MINECRAFT_1_7_2, Some("2013-10-25")),
+    ("1.7.3", MINECRAFT_1_7_3, None),
+    ("1.7.4", MINECRAFT_1_7_4, Some("2013-12-10")),
+    ("1.7.5", MINECRAFT_1_7_5, Some("2014-02-26")),
+    ("1.7.6", MINECRAFT_1_7_6, Some("2014-04-09")),
+    ("1.7.7", MINECRAFT_1_7_7, Some("2014-04-09")),
+    ("1.7.8", MINECRAFT_1_7_8, Some("2014-04-11")),
+    ("1.7.9", MINECRAFT_1_7_9, Some("2014-04-14")),
+    ("1.7.10", MINECRAFT_1_7_10, Some("2014-06-26")),
+    ("1.8", MINECRAFT_1_8, Some("2014-09-02")),
+    ("1.8.1", MINECRAFT_1_8_1, Some("2014-11-24")),
+    ("1.8.2", MINECRAFT_1_8_2, Some("2015-02-19")),
+    ("1.8.3", MINECRAFT_1_8_3, Some("2015-02-20")),
+    ("1.8.4", MINECRAFT_1_8_4, Some("2015-04-17")),
+    ("1.8.5", MINECRAFT_1_8_5, Some("2015-05-22")),
+    ("1.8.6", MINECRAFT_1_8_6, Some("2015-05-25")),
+    ("1.8.7", MINECRAFT_1_8_7, Some("2015-06-05")),
+    ("1.8.8", MINECRAFT_1_8_8, Some("2015-07-28")),
+    ("1.8.9", MINECRAFT_1_8_9, Some("2015-12-09")),
+    ("1.9", MINECRAFT_1_9, Some("2016-02-29")),
+    ("1.9.1", MINECRAFT_1_9_1, Some("2016-03-30")),
+    ("1.9.2", MINECRAFT_1_9_2, Some("2016-03-30")),
+    ("1.9.3", MINECRAFT_1_9_3, Some("2016-05-10")),
+    ("1.9.4", MINECRAFT_1_9_4, Some("2016-05-10")),
+    ("1.10", MINECRAFT_1_10, Some("2016-06-08")),
+    ("1.10.1", MINECRAFT_1_10_1, Some("2016-06-22")),
+    ("1.10.2", MINECRAFT_1_10_2, Some("2016-06-23")),
+    ("1.11", MINECRAFT_1_11, Some("2016-11-14")),
+    ("1.11.1", MINECRAFT_1_11_1, Some("2016-12-20")),
+    ("1.11.2", MINECRAFT_1_11_2, Some("2016-12-21")),
+    ("1.12", MINECRAFT_1_12, Some("2017-06-07")),
+    ("1.12.1", MINECRAFT_1_12_1, Some("2017-08-03")),
+    ("1.12.2", MINECRAFT_1_12_2, Some("2017-09-18")),
+    ("1.13", MINECRAFT_1_13, Some("2018-07-18")),
+    ("1.13.1", MINECRAFT_1_13_1, Some("2018-08-22")),
+    ("1.13.2", MINECRAFT_1_13_2, Some("2018-10-22")),
+    ("1.14", MINECRAFT_1_14, Some("2019-04-23")),
+    ("1.14.1", MINECRAFT_1_14_1, Some("2019-05-13")),
+    ("1.14.2", MINECRAFT_1_14_2, Some("2019-05-27")),
+    ("1.14.3", MINECRAFT_1_14_3, Some("2019-06-24")),
+    ("1.14.4", MINECRAFT_1_14_4, Some("2019-07-19")),
+    ("1.15", MINECRAFT_1_15, Some("2019-12-10")),
+    ("1.15.1", MINECRAFT_1_15_1, Some("2019-12-17")),
+    ("1.15.2", MINECRAFT_1_15_2, Some("2020-01-21")),
+    ("1.16", MINECRAFT_1_16, Some("2020-06-23")),
+    ("1.16.1", MINECRAFT_1_16_1, Some("2020-06-24")),
+    ("1.16.2", MINECRAFT_1_16_2, Some("2020-08-11")),
+    ("1.16.3", MINECRAFT_1_16_3, Some("2020-09-10")),
+    ("1.16.4", MINECRAFT_1_16_4, Some("2020-11-02")),
+    ("1.16.5", MINECRAFT_1_16_5, Some("2021-01-15")),
+    ("1.17", MINECRAFT_1_17, Some("2021-06-08")),
+    ("1.17.1", MINECRAFT_1_17_1, Some("2021-07-06")),
+    ("1.18", MINECRAFT_1_18, Some("2021-11-30")),
+    ("1.18.1", MINECRAFT_1_18_1, Some("2021-12-10")),
+    ("1.18.2", MINECRAFT_1_18_2, Some("2022-02-28")),
+    ("1.19", MINECRAFT_1_19, Some("2022-06-07")),
+    ("1.19.1", MINECRAFT_1_19_1, Some("2022-07-27")),
+    ("1.19.2", MINECRAFT_1_19_2, Some("2022-08-05")),
+    ("1.19.3", MINECRAFT_1_19_3, Some("2022-12-07")),
+    ("1.19.4", MINECRAFT_1_19_4, Some("2023-03-14")),
+    ("1.20", MINECRAFT_1_20, Some("2023-06-07")),
+    ("1.20.1", MINECRAFT_1_20_1, Some("2023-06-12")),
+    ("1.20.2", MINECRAFT_1_20_2, Some("2023-09-21")),
+    ("1.20.3", MINECRAFT_1_20_3, Some("2023-12-05")),
+    ("1.20.4", MINECRAFT_1_20_4, Some("2023-12-07")),
+    ("1.20.5", MINECRAFT_1_20_5, Some("2024-04-23")),
+    ("1.20.6", MINECRAFT_1_20_6, Some("2024-04-29")),
+    ("1.21", MINECRAFT_1_21, Some("2024-06-13")),
+    ("1.21.1", MINECRAFT_1_21_1, Some("2024-08-08")),
+    ("1.21.2", MINECRAFT_1_21_2, Some("2024-10-22")),
+    ("1.21.3", MINECRAFT_1_21_3, Some("2024-10-23")),
+    ("1.21.4", MINECRAFT_1_21_4, Some("2024-12-03")),
+    ("1.21.5", MINECRAFT_1_21_5, Some("2025-03-25")),
+    ("1.21.6", MINECRAFT_1_21_6, Some("2025-06-17")),
+    ("1.21.7", MINECRAFT_1_21_7, Some("2025-06-30")),
+    ("1.21.8", MINECRAFT_1_21_8, Some("2025-07-17")),
+];
+
+/// Named pre-releases and release candidates, minecraft-data lists the others.
+/// Snapshots since 1.16.4-pre1 are also recognised by their protocol number.
+const BUILTIN_SNAPSHOTS: &[(&str, ReleaseType, ProtocolNum)] = &[
+    ("1.14.1-pre1", ReleaseType::PreRelease, 478),
+    ("1.14.1-pre2", ReleaseType::PreRelease, 479),
+    ("1.14.2-pre1", ReleaseType::PreRelease, 481),
+    ("1.14.2-pre2", ReleaseType::PreRelease, 482),
+    ("1.14.2-pre3", ReleaseType::PreRelease, 483),
+    ("1.14.2-pre4", ReleaseType::PreRelease, 484),
+    ("1.14.3-pre1", ReleaseType::PreRelease, 486),
+    ("1.14.3-pre2", ReleaseType::PreRelease, 487),
+    ("1.14.3-pre3", ReleaseType::PreRelease, 488),
+    ("1.14.3-pre4", ReleaseType::PreRelease, 489),
+    ("1.14.4-pre1", ReleaseType::PreRelease, 491),
+    ("1.14.4-pre2", ReleaseType::PreRelease, 492),
+    ("1.14.4-pre3", ReleaseType::PreRelease, 493),
+    ("1.14.4-pre4", ReleaseType::PreRelease, 494),
+    ("1.14.4-pre5", ReleaseType::PreRelease, 495),
+    ("1.14.4-pre6", ReleaseType::PreRelease, 496),
+    ("1.14.4-pre7", ReleaseType::PreRelease, 497),
+    ("1.15.1-pre1", ReleaseType::PreRelease, 574),
+    ("1.15.2-pre1", ReleaseType::PreRelease, 576),
+    ("1.15.2-pre2", ReleaseType::PreRelease, 577),
+    ("1.16-rc1", ReleaseType::ReleaseCandidate, 734),
+    ("1.16.2-rc1", ReleaseType::ReleaseCandidate, 749),
+    ("1.16.2-rc2", ReleaseType::ReleaseCandidate, 750),
+    ("1.16.3-rc1", ReleaseType::ReleaseCandidate, 752),
+    ("1.16.4-pre1", ReleaseType::PreRelease, snapshot_protocol(1)),
+    ("1.16.4-pre2", ReleaseType::PreRelease, snapshot_protocol(2)),
+    ("1.16.4-rc1", ReleaseType::ReleaseCandidate, snapshot_protocol(3)),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReleaseType {
+    Release,
+    Snapshot,
+    PreRelease,
+    ReleaseCandidate,
+}
+
+/// What a protocol number stands for, see [`VersionRegistry::classify`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProtocolKind {
+    /// Number of one or more versions of the registry
+    Named,
+    /// Snapshot, pre-release or release candidate numbered `0x40000000 | n` since 1.16.4-pre1
+    Snapshot(i32),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameVersion {
+    pub name: String,
+    pub protocol: ProtocolNum,
+    /// Release date as YYYY-MM-DD
+    pub release_date: Option<String>,
+    pub release_type: ReleaseType,
+}
+
+/// Entry of the `protocolVersions.json` of PrismarineJS minecraft-data, newest first in the file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MinecraftDataVersion {
+    minecraft_version: String,
+    version: ProtocolNum,
+    /// False for pre-netty versions, whose numbers overlap with netty ones
+    #[serde(default)]
+    uses_netty: bool,
+    #[serde(default)]
+    release_type: String,
+}
+
+impl MinecraftDataVersion {
+    fn into_game_version(self) -> GameVersion {
+        let release_type = if self.minecraft_version.contains("-pre") {
+            ReleaseType::PreRelease
+        } else if self.minecraft_version.contains("-rc") {
+            ReleaseType::ReleaseCandidate
+        } else if self.release_type == "release" {
+            ReleaseType::Release
+        } else {
+            ReleaseType::Snapshot
+        };
+        GameVersion { name: self.minecraft_version, protocol: self.version, release_date: None, release_type }
+    }
+}
+
+fn installed() -> &'static OnceLock<VersionRegistry> {
+    static INSTALLED: OnceLock<VersionRegistry> = OnceLock::new();
+    &INSTALLED
+}
+
+/// Known versions and their protocol numbers, looked up in both directions
+#[derive(Clone, Debug, Default)]
+pub struct VersionRegistry {
+    /// Oldest first
+    versions: Vec<GameVersion>,
+}
+
+impl VersionRegistry {
+    /// Registry of the versions compiled into this crate
+    pub fn builtin() -> &'static VersionRegistry {
+        static BUILTIN: OnceLock<VersionRegistry> = OnceLock::new();
+        BUILTIN.get_or_init(|| {
+            let releases = BUILTIN_VERSIONS.iter().map(|(name, protocol, release_date)| GameVersion {
+                name: name.to_string(),
+                protocol: *protocol,
+                release_date: release_date.map(|v| v.to_string()),
+                release_type: ReleaseType::Release,
+            });
+            let snapshots = BUILTIN_SNAPSHOTS.iter().map(|(name, release_type, protocol)| GameVersion {
+                name: name.to_string(),
+                protocol: *protocol,
+                release_date: None,
+                release_type: *release_type,
+            });
+            VersionRegistry { versions: releases.chain(snapshots).collect() }
+        })
+    }
+
+    /// Registry installed with [`VersionRegistry::install`], or the compiled-in one
+    pub fn current() -> &'static VersionRegistry {
+        installed().get().unwrap_or_else(|| VersionRegistry::builtin())
+    }
+
+    /// Makes this registry the one used for protocol checks and version names in the whole process.
+    /// Can only be done once, the registry is handed back if one is installed already.
+    pub fn install(self) -> Result<(), VersionRegistry> {
+        installed().set(self)
+    }
+
+    /// Compiled-in versions merged with a minecraft-data `protocolVersions.json` file
+    pub fn from_minecraft_data_file(path: &Path) -> io::Result<VersionRegistry> {
+        let mut registry = VersionRegistry::builtin().clone();
+        registry.merge_minecraft_data(&fs::read_to_string(path)?)?;
+        Ok(registry)
+    }
+
+    /// Merges versions in the minecraft-data `protocolVersions.json` format, pre-netty ones are skipped.
+    /// Returns how many versions were merged.
+    pub fn merge_minecraft_data(&mut self, json: &str) -> Result<usize, serde_json::Error> {
+        let entries: Vec<MinecraftDataVersion> = serde_json::from_str(json)?;
+        let versions: Vec<GameVersion> = entries.into_iter()
+            .rev()
+            .filter(|v| v.uses_netty)
+            .map(MinecraftDataVersion::into_game_version)
+            .collect();
+        let count = versions.len();
+        self.merge(versions);
+        Ok(count)
+    }
+
+    /// Adds versions given oldest first. A version with the name of a known one replaces it,
+    /// keeping the known release date if it has none.
+    pub fn merge(&mut self, versions: impl IntoIterator<Item = GameVersion>) {
+        for mut version in versions {
+            match self.versions.iter_mut().find(|v| v.name == version.name) {
+                Some(known) => {
+                    if version.release_date.is_none() {
+                        version.release_date = known.release_date.take();
+                    }
+                    *known = version;
+                }
+                None => self.versions.push(version),
+            }
+        }
+        // Stable, versions sharing a number keep their order
+        self.versions.sort_by_key(|v| v.protocol);
+    }
+
+    pub fn versions(&self) -> &[GameVersion] {
+        &self.versions
+    }
+
+    /// All versions sharing a protocol number, oldest first
+    pub fn by_protocol(&self, protocol: ProtocolNum) -> Vec<&GameVersion> {
+        self.versions.iter().filter(|v| v.protocol == protocol).collect()
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&GameVersion> {
+        self.versions.iter().find(|v| v.name == name)
+    }
+
+    /// True for the numbers of versions in the registry, snapshots and pre-releases
+    pub fn is_known(&self, protocol: ProtocolNum) -> bool {
+        self.classify(protocol).is_some()
+    }
+
+    /// Tells versions of the registry from unnamed snapshots and pre-releases, `None` for unknown numbers
+    pub fn classify(&self, protocol: ProtocolNum) -> Option<ProtocolKind> {
+        if self.versions.iter().any(|v| v.protocol == protocol) {
+            return Some(ProtocolKind::Named);
+        }
+        if is_snapshot_protocol_number(protocol) {
+            return Some(ProtocolKind::Snapshot(protocol & !SNAPSHOT_PROTOCOL_BIT));
+        }
+        None
+    }
+
+    /// Distinct protocol numbers of releases, ascending
+    pub fn protocols(&self) -> Vec<ProtocolNum> {
+        let mut res: Vec<ProtocolNum> = self.versions.iter()
+            .filter(|v| v.release_type == ReleaseType::Release)
+            .map(|v| v.protocol)
+            .collect();
+        res.sort();
+        res.dedup();
+        res
+    }
+
+    /// Versions sharing a protocol number as a range, `1.8–1.8.9`, or a single name.
+    /// Releases are preferred over pre-releases sharing their number.
+    /// Unnamed snapshots read `snapshot 0x40000105`.
+    pub fn display_name(&self, protocol: ProtocolNum) -> Option<String> {
+        let mut versions = self.by_protocol(protocol);
+        if versions.iter().any(|v| v.release_type == ReleaseType::Release) {
+            versions.retain(|v| v.release_type == ReleaseType::Release);
+        }
+        match versions.as_slice() {
+            [] => {}
+            [single] => return Some(single.name.clone()),
+            [first, .., last] => return Some(format!("{}\u{2013}{}", first.name, last.name)),
+        }
+        match self.classify(protocol)? {
+            ProtocolKind::Named => None,
+            ProtocolKind::Snapshot(_) => Some(format!("snapshot {protocol:#x}")),
+        }
+    }
+
+    /// Parses a protocol number, in decimal or `0x` prefixed hexadecimal, or a version name such as `1.20.4`
+    pub fn parse_protocol(&self, s: &str) -> Result<ProtocolNum, String> {
+        if let Ok(protocol) = ProtocolNum::from_str(s) {
+            return Ok(protocol);
+        }
+        if let Some(Ok(protocol)) = s.strip_prefix("0x").map(|v| ProtocolNum::from_str_radix(v, 16)) {
+            return Ok(protocol);
+        }
+        self.by_name(s).map(|v| v.protocol).ok_or_else(|| format!("Unknown protocol number or version \"{s}\""))
+    }
+}
+
+/// Protocol number or version name of the current registry, as accepted by `--protocol`
+pub fn parse_protocol(s: &str) -> Result<ProtocolNum, String> {
+    VersionRegistry::current().parse_protocol(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::{is_known_protocol_number, LATEST};
+
+    use super::*;
+
+    #[test]
+    fn test_by_protocol() {
+        let registry = VersionRegistry::builtin();
+        let names: Vec<&str> = registry.by_protocol(MINECRAFT_1_8).iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(vec!["1.8", "1.8.1", "1.8.2", "1.8.3", "1.8.4", "1.8.5", "1.8.6", "1.8.7", "1.8.8", "1.8.9"], names);
+        assert!(registry.by_protocol(MINECRAFT_1_8 + 1).is_empty());
+        assert_eq!(Some("2014-09-02".to_string()), registry.by_protocol(MINECRAFT_1_8)[0].release_date);
+    }
+
+    #[test]
+    fn test_by_name() {
+        let registry = VersionRegistry::builtin();
+        assert_eq!(Some(MINECRAFT_1_20_4), registry.by_name("1.20.4").map(|v| v.protocol));
+        assert_eq!(Some(MINECRAFT_1_7_10), registry.by_name("1.7.10").map(|v| v.protocol));
+        assert!(registry.by_name("1.20.7").is_none());
+    }
+
+    #[test]
+    fn test_display_name() {
+        let registry = VersionRegistry::builtin();
+        assert_eq!(Some("1.8\u{2013}1.8.9".to_string()), registry.display_name(MINECRAFT_1_8));
+        assert_eq!(Some("1.21.7\u{2013}1.21.8".to_string()), registry.display_name(MINECRAFT_1_21_8));
+        assert_eq!(Some("1.21.4".to_string()), registry.display_name(MINECRAFT_1_21_4));
+        assert_eq!(Some("1.16.4-pre1".to_string()), registry.display_name(snapshot_protocol(1)));
+        assert_eq!(Some("snapshot 0x40000105".to_string()), registry.display_name(snapshot_protocol(0x105)));
+        assert_eq!(Some("1.16.2-rc2".to_string()), registry.display_name(750));
+        assert!(registry.display_name(MINECRAFT_1_8 + 1).is_none());
+        assert!(registry.display_name(LATEST + 1).is_none());
+    }
+
+    #[test]
+    fn test_classify() {
+        let registry = VersionRegistry::builtin();
+        assert_eq!(Some(ProtocolKind::Named), registry.classify(MINECRAFT_1_12_2));
+        assert_eq!(Some(ProtocolKind::Named), registry.classify(snapshot_protocol(1)));
+        assert_eq!(Some(ProtocolKind::Snapshot(42)), registry.classify(snapshot_protocol(42)));
+        assert_eq!(Some(ProtocolKind::Named), registry.classify(752));
+        // Numbers between releases are only known when a pre-release used them
+        assert_eq!(None, registry.classify(MINECRAFT_1_8 + 1));
+        assert_eq!(None, registry.classify(150));
+        assert_eq!(None, registry.classify(MINECRAFT_1_12 + 1));
+        assert_eq!(None, registry.classify(MINECRAFT_1_7 - 1));
+        assert_eq!(None, registry.classify(LATEST + 1));
+        assert_eq!(None, registry.classify(SNAPSHOT_PROTOCOL_BIT));
+        assert_eq!(None, registry.classify(0x7FFFFFFF));
+        assert_eq!(None, registry.classify(-1));
+    }
+
+    #[test]
+    fn test_parse_protocol() {
+        assert_eq!(Ok(MINECRAFT_1_20_4), parse_protocol("1.20.4"));
+        assert_eq!(Ok(765), parse_protocol("765"));
+        assert_eq!(Ok(1), parse_protocol("1"));
+        assert_eq!(Ok(snapshot_protocol(1)), parse_protocol("0x40000001"));
+        assert_eq!(Ok(snapshot_protocol(1)), parse_protocol("1.16.4-pre1"));
+        assert!(parse_protocol("1.20.9").is_err());
+        assert!(parse_protocol("latest").is_err());
+    }
+
+    #[test]
+    fn test_merge_minecraft_data() {
+        let json = r#"[
+            {"minecraftVersion":"1.21.10","version":773,"dataVersion":4556,"usesNetty":true,"majorVersion":"1.21","releaseType":"release"},
+            {"minecraftVersion":"1.21.10-rc1","version":1073742097,"dataVersion":4553,"usesNetty":true,"majorVersion":"1.21","releaseType":"snapshot"},
+            {"minecraftVersion":"25w37a","version":1073742094,"dataVersion":4544,"usesNetty":true,"majorVersion":"1.21","releaseType":"snapshot"},
+            {"minecraftVersion":"1.21.8","version":772,"usesNetty":true,"majorVersion":"1.21","releaseType":"release"},
+            {"minecraftVersion":"1.16.5-rc1","version":754,"usesNetty":true,"majorVersion":"1.16","releaseType":"snapshot"},
+            {"minecraftVersion":"1.6.4","version":78,"usesNetty":false,"majorVersion":"1.6","releaseType":"release"}
+        ]"#;
+        let mut registry = VersionRegistry::builtin().clone();
+        assert_eq!(5, registry.merge_minecraft_data(json).unwrap());
+
+        let latest = registry.by_name("1.21.10").unwrap();
+        assert_eq!(773, latest.protocol);
+        assert_eq!(ReleaseType::Release, latest.release_type);
+        assert_eq!(Some(&773), registry.protocols().last());
+        assert_eq!(Some("2025-07-17".to_string()), registry.by_name("1.21.8").unwrap().release_date.clone());
+        assert_eq!(ReleaseType::ReleaseCandidate, registry.by_name("1.21.10-rc1").unwrap().release_type);
+        assert_eq!(Some("25w37a".to_string()), registry.display_name(snapshot_protocol(270)));
+        assert_eq!(Some("1.16.4\u{2013}1.16.5".to_string()), registry.display_name(MINECRAFT_1_16_4));
+        assert!(registry.by_name("1.6.4").is_none());
+        assert!(registry.is_known(773));
+        assert!(!VersionRegistry::builtin().is_known(773));
+
+        assert!(registry.merge_minecraft_data("{}").is_err());
+    }
+
+    #[test]
+    fn test_from_minecraft_data_file() {
+        let path = std::env::temp_dir().join(format!("rolling-glass-versions-{}.json", std::process::id()));
+        fs::write(&path, r#"[{"minecraftVersion":"1.21.10","version":773,"usesNetty":true,"releaseType":"release"}]"#).unwrap();
+        let registry = VersionRegistry::from_minecraft_data_file(&path);
+        let _ = fs::remove_file(&path);
+        assert_eq!(Ok(773), registry.unwrap().parse_protocol("1.21.10"));
+        assert!(VersionRegistry::from_minecraft_data_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_protocols() {
+        let protocols = VersionRegistry::builtin().protocols();
+        assert_eq!(Some(&MINECRAFT_1_7), protocols.first());
+        assert_eq!(Some(&LATEST), protocols.last());
+        assert!(!protocols.contains(&snapshot_protocol(1)));
+        assert!(protocols.windows(2).all(|w| w[0] < w[1]));
+        assert!(protocols.iter().all(|v| is_known_protocol_number(*v)));
+    }
+}
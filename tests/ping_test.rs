@@ -1,17 +1,52 @@
 use std::io::{stdout, Write};
+use std::net::IpAddr;
 
-use rolling_glass::{DEFAULT_PORT, LATEST, MINECRAFT_1_7, MINECRAFT_1_8, ping};
+use rolling_glass::{DEFAULT_PORT, IpMode, LATEST, MINECRAFT_1_7, MINECRAFT_1_8, ping, ping_status, ping_with_options, PingError, PingOptions};
+use rolling_glass::testing::{MockPong, MockScript, MockServer, MockStatusScript, StubGuard, StubResolver};
+
+/// Stands in for CubeCraft, reached through an SRV record like the real one.
+/// Each test stubs its own host as the installed stubs are shared by the whole test binary.
+async fn cubecraft(host: &str) -> (MockServer, StubGuard) {
+    let server = MockServer::start(MockStatusScript {
+        json: r#"{"version":{"name":"CubeCraft","protocol":772},"players":{"max":50000,"online":12345},"description":{"text":"CubeCraft Games"}}"#.to_string(),
+        ..Default::default()
+    }).await;
+    let stub = StubResolver::new()
+        .srv(host, "mc.cubecraft.net.", server.port())
+        .ip("mc.cubecraft.net", IpAddr::from([127, 0, 0, 1]))
+        .install();
+    (server, stub)
+}
+
+/// Stands in for mc.hypixel.net, which drops connections addressed to unknown hosts
+async fn hypixel() -> (MockServer, StubGuard) {
+    let server = MockServer::start(MockScript {
+        virtual_host: Some("mc.hypixel.net".to_string()),
+        ..Default::default()
+    }).await;
+    let stub = StubResolver::new()
+        .srv("mc.hypixel.net", "mc.hypixel.net.", server.port())
+        .ip("mc.hypixel.net", IpAddr::from([127, 0, 0, 1]))
+        .install();
+    (server, stub)
+}
 
 #[tokio::test]
 async fn test_ping() {
-    assert!(ping(&String::new(), DEFAULT_PORT, &String::new(), MINECRAFT_1_8, 3).await.is_err());
-    assert!(ping(&String::from("::1"), DEFAULT_PORT, &String::new(), MINECRAFT_1_8, 3).await.is_err());
+    let (cubecraft, _cubecraft_stub) = cubecraft("play.cubecraft.net").await;
+    let (hypixel, _hypixel_stub) = hypixel().await;
+    let _missing = StubResolver::new().unresolvable("doesntexist.local").install();
+
+    assert!(ping("", DEFAULT_PORT, &String::new(), MINECRAFT_1_8, 3).await.is_err());
     assert!(ping(&String::from("127.0.0.1"), DEFAULT_PORT, &String::new(), MINECRAFT_1_7 - 1, 3).await.is_err());
     assert!(ping(&String::from("127.0.0.1"), DEFAULT_PORT, &String::new(), LATEST + 1, 3).await.is_err());
     assert!(ping(&String::from("127.0.0.1"), DEFAULT_PORT, &String::new(), LATEST + 1, 0).await.is_err());
     assert!(ping(&String::from("doesntexist.local"), DEFAULT_PORT, &String::new(), LATEST + 1, 0).await.is_err());
+    assert!(matches!(ping(&String::from("doesntexist.local"), DEFAULT_PORT, &String::new(), LATEST, 1).await, Err(PingError::Resolve(_))));
     assert!(ping(&String::from("127.0.0.1"), DEFAULT_PORT, &String::new(), MINECRAFT_1_8, 1).await.is_err());
     assert!(ping(&String::from("mc.hypixel.net"), DEFAULT_PORT, &String::from("hypixel.gg"), LATEST, 3).await.is_err());
+    assert!(ping(&String::from("mc.hypixel.net"), DEFAULT_PORT, &String::new(), LATEST, 3).await.is_ok());
+    assert_eq!(2, hypixel.handshakes().len());
 
     let res = ping(
         &String::from("play.cubecraft.net"),
@@ -23,4 +58,68 @@ async fn test_ping() {
     let res = res.unwrap();
     assert!(!res.is_empty());
     let _ = stdout().write_all(&res);
+
+    let handshakes = cubecraft.handshakes();
+    assert_eq!(1, handshakes.len());
+    assert_eq!("cubecraft.gg", handshakes[0].host);
+    assert_eq!(DEFAULT_PORT, handshakes[0].port);
+    assert_eq!(LATEST, handshakes[0].protocol);
+}
+
+#[tokio::test]
+async fn test_ping_ipv6() {
+    let server = MockServer::start_on(IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1]), MockScript::default()).await;
+    for (ip_mode, reachable) in [(IpMode::Dual, true), (IpMode::V6Only, true), (IpMode::V4Only, false)] {
+        let options = PingOptions { protocol: MINECRAFT_1_8, timeout: 3, ip_mode, ..Default::default() };
+        let res = ping_with_options("::1", server.port(), &options).await;
+        assert_eq!(reachable, res.is_ok(), "{ip_mode:?}");
+        if !reachable {
+            assert!(matches!(res, Err(PingError::AddressFamily(_, IpMode::V4Only))));
+        }
+    }
+    assert_eq!(2, server.handshakes().len());
+}
+
+#[tokio::test]
+async fn test_ping_status() {
+    let (_cubecraft, _stub) = cubecraft("cubecraft.net").await;
+
+    assert!(ping_status("", DEFAULT_PORT, &String::new(), MINECRAFT_1_8, 3).await.is_err());
+
+    let res = ping_status(
+        &String::from("cubecraft.net"),
+        DEFAULT_PORT,
+        &String::from("cubecraft.gg"),
+        LATEST,
+    3).await;
+    assert!(res.is_ok());
+    let res = res.unwrap();
+    assert!(!res.raw.is_empty());
+    assert!(res.response.version.is_some());
+    assert!(res.response.players.is_some());
+    assert_eq!("CubeCraft Games", res.response.description_text());
+}
+
+#[tokio::test]
+async fn test_ping_status_bad_responses() {
+    let server = MockServer::start(MockStatusScript { json: "{\"version\":".to_string(), ..Default::default() }).await;
+    let res = ping_status("127.0.0.1", server.port(), &String::new(), MINECRAFT_1_8, 3).await;
+    assert!(matches!(res, Err(PingError::InvalidJson(_))));
+
+}
+
+#[tokio::test]
+async fn test_ping_status_without_pong() {
+    let server = MockServer::start(MockStatusScript::default()).await;
+    let res = ping_status("127.0.0.1", server.port(), &String::new(), MINECRAFT_1_8, 3).await.unwrap();
+    assert!(res.latency.is_some());
+
+    // The status is kept when the server does not answer the Ping Request properly
+    for pong in [MockPong::Payload(1), MockPong::Silent, MockPong::Close] {
+        let server = MockServer::start(MockStatusScript { pong: pong.clone(), ..Default::default() }).await;
+        let res = ping_status("127.0.0.1", server.port(), &String::new(), MINECRAFT_1_8, 1).await;
+        let res = res.unwrap_or_else(|e| panic!("{pong:?}: {e}"));
+        assert_eq!("A mock server", res.response.description_text(), "{pong:?}");
+        assert_eq!(None, res.latency, "{pong:?}");
+    }
 }
@@ -0,0 +1,72 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use rolling_glass::{compose_rcon_packet, RconClient, RconError, RconOptions};
+
+const PASSWORD: &str = "hunter2";
+
+async fn read_request(conn: &mut TcpStream) -> Option<(i32, i32, String)> {
+    let len = conn.read_i32_le().await.ok()?;
+    let mut content = vec![0u8; len as usize];
+    conn.read_exact(&mut content).await.ok()?;
+    let request_id = i32::from_le_bytes(content[0..4].try_into().unwrap());
+    let packet_type = i32::from_le_bytes(content[4..8].try_into().unwrap());
+    let body = String::from_utf8_lossy(&content[8..content.len() - 2]).to_string();
+    Some((request_id, packet_type, body))
+}
+
+/// Stand-in for the vanilla RCON listener, fragments responses at 4096 bytes
+async fn spawn_rcon_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut conn, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut authenticated = false;
+                while let Some((request_id, packet_type, body)) = read_request(&mut conn).await {
+                    let replies = match packet_type {
+                        3 if body == PASSWORD => {
+                            authenticated = true;
+                            vec![compose_rcon_packet(request_id, 2, "")]
+                        }
+                        3 => vec![compose_rcon_packet(-1, 2, "")],
+                        2 if authenticated && body == "long" => {
+                            let res = "x".repeat(10000);
+                            res.as_bytes().chunks(4096)
+                                .map(|c| compose_rcon_packet(request_id, 0, std::str::from_utf8(c).unwrap()))
+                                .collect()
+                        }
+                        2 if authenticated => vec![compose_rcon_packet(request_id, 0, &format!("Ran {body}"))],
+                        _ => vec![compose_rcon_packet(request_id, 0, &format!("Unknown request {packet_type:x}"))],
+                    };
+                    for reply in replies {
+                        if conn.write_all(&reply).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    port
+}
+
+#[tokio::test]
+async fn test_rcon() {
+    let port = spawn_rcon_server().await;
+    let options = RconOptions { timeout: 2, ..Default::default() };
+
+    let no_timeout = RconOptions { timeout: 0, ..options };
+    assert!(matches!(RconClient::connect("127.0.0.1", port, PASSWORD, &no_timeout).await, Err(RconError::InvalidTimeout)));
+
+    let client = RconClient::connect("127.0.0.1", port, "wrong", &options).await;
+    assert!(matches!(client, Err(RconError::AuthFailed)));
+
+    let client = RconClient::connect("127.0.0.1", port, PASSWORD, &options).await;
+    assert!(client.is_ok());
+    let mut client = client.unwrap();
+    assert_eq!("Ran list", client.command("list").await.unwrap());
+    assert_eq!("Ran say hi", client.command("say hi").await.unwrap());
+    assert_eq!("x".repeat(10000), client.command("long").await.unwrap());
+    client.close().await;
+}